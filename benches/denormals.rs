@@ -0,0 +1,54 @@
+//! Times a comb filter ringing out into silence, with and without `DenormalGuard`.
+//!
+//! Run with `cargo bench --bench denormals`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use libaudiotest::denormal::DenormalGuard;
+
+const DELAY: usize = 1021;
+const FEEDBACK: f32 = 0.7;
+const BLOCK: usize = 1024;
+const BLOCKS: usize = 4000;
+
+/// Feeds a burst of noise into a feedback comb filter and then keeps processing silence, which is
+/// exactly how a reverb or echo tail decays into the denormal range.
+fn ring_out() -> Duration {
+    let mut delay = vec![0.0f32; DELAY];
+    let mut pos = 0;
+    let mut seed = 1u32;
+    let mut input: Vec<f32> = (0..BLOCK)
+        .map(|_| {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed >> 8) as f32 / (1 << 24) as f32 - 0.5
+        })
+        .collect();
+
+    let start = Instant::now();
+    for block in 0..BLOCKS {
+        for &x in &input {
+            let y = x + delay[pos] * FEEDBACK;
+            delay[pos] = black_box(y);
+            pos = if pos + 1 == DELAY { 0 } else { pos + 1 };
+        }
+        if block == 0 {
+            input.fill(0.0);
+        }
+    }
+    black_box(&delay);
+    start.elapsed()
+}
+
+fn main() {
+    let plain = ring_out();
+    let flushed = {
+        let _denormals = DenormalGuard::enable();
+        ring_out()
+    };
+
+    println!("decaying feedback loop, {BLOCKS} blocks of {BLOCK} samples");
+    println!("  default FP mode:   {plain:?}");
+    println!("  with DenormalGuard: {flushed:?}");
+    println!("  speed-up:           {:.1}x", plain.as_secs_f64() / flushed.as_secs_f64());
+}
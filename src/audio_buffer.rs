@@ -0,0 +1,284 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+/// Read-only view of an interleaved block of `frames * channels` samples.
+#[derive(Clone, Copy)]
+pub struct Interleaved<'a> {
+    samples: &'a [f32],
+    frames: usize,
+    channels: usize,
+}
+
+/// Mutable view of an interleaved block of `frames * channels` samples.
+pub struct InterleavedMut<'a> {
+    samples: &'a mut [f32],
+    frames: usize,
+    channels: usize,
+}
+
+impl<'a> Interleaved<'a> {
+    /// A view with no channels, standing in for a missing buffer such as an inactive side-chain.
+    pub fn empty(frames: usize) -> Self {
+        Self { samples: &[], frames, channels: 0 }
+    }
+
+    /// # Safety
+    /// `ptr` must point to `frames * channels` readable samples that outlive `'a`.
+    pub unsafe fn from_raw(ptr: *const f32, frames: usize, channels: usize) -> Self {
+        if ptr.is_null() || channels == 0 {
+            return Self::empty(frames);
+        }
+        Self { samples: std::slice::from_raw_parts(ptr, frames * channels), frames, channels }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn samples(&self) -> &'a [f32] {
+        self.samples
+    }
+
+    pub fn frame(&self, n: usize) -> &'a [f32] {
+        &self.samples[n * self.channels..(n + 1) * self.channels]
+    }
+
+    /// `frames` frames starting at frame `start`.
+    pub fn slice_frames(&self, start: usize, frames: usize) -> Self {
+        let samples = &self.samples[start * self.channels..(start + frames) * self.channels];
+        Self { samples, frames, channels: self.channels }
+    }
+}
+
+impl<'a> InterleavedMut<'a> {
+    /// # Safety
+    /// `ptr` must point to `frames * channels` writable samples that outlive `'a` and are not aliased.
+    pub unsafe fn from_raw(ptr: *mut f32, frames: usize, channels: usize) -> Self {
+        if ptr.is_null() || channels == 0 {
+            return Self { samples: &mut [], frames, channels: 0 };
+        }
+        Self { samples: std::slice::from_raw_parts_mut(ptr, frames * channels), frames, channels }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        self.samples
+    }
+
+    pub fn samples_mut(&mut self) -> &mut [f32] {
+        self.samples
+    }
+
+    pub fn frame_mut(&mut self, n: usize) -> &mut [f32] {
+        &mut self.samples[n * self.channels..(n + 1) * self.channels]
+    }
+
+    pub fn iter_frames_mut(&mut self) -> impl Iterator<Item = &mut [f32]> {
+        // chunks_exact panics on 0, and a channel-less view has nothing to iterate anyway.
+        self.samples.chunks_exact_mut(self.channels.max(1))
+    }
+
+    pub fn fill(&mut self, value: f32) {
+        self.samples.fill(value);
+    }
+}
+
+/// Copies `input` into `output`, up- or down-mixing when the channel counts differ.
+///
+/// Mono is spread to every output channel, anything folds down to mono by averaging, and 5.1 / 7.1
+/// (Unity's FL, FR, C, LFE, surround order) fold down to stereo with the usual -3 dB centre and
+/// surround gains. Any other combination copies the shared channels and silences the rest.
+pub fn remix(input: &Interleaved, output: &mut InterleavedMut) {
+    let in_ch = input.channels();
+    let out_ch = output.channels();
+    let frames = input.frames().min(output.frames());
+
+    if in_ch == 0 {
+        output.fill(0.0);
+        return;
+    }
+
+    if in_ch == out_ch {
+        let len = frames * in_ch;
+        output.samples_mut()[..len].copy_from_slice(&input.samples()[..len]);
+        return;
+    }
+
+    for n in 0..frames {
+        let src = input.frame(n);
+        let dst = output.frame_mut(n);
+        match (in_ch, out_ch) {
+            (1, _) => dst.fill(src[0]),
+            (_, 1) => dst[0] = src.iter().sum::<f32>() / in_ch as f32,
+            (6, 2) | (8, 2) => {
+                let centre = src[2] * FRAC_1_SQRT_2;
+                let (mut left, mut right) = (src[0] + centre, src[1] + centre);
+                for pair in src[4..].chunks_exact(2) {
+                    left += pair[0] * FRAC_1_SQRT_2;
+                    right += pair[1] * FRAC_1_SQRT_2;
+                }
+                dst[0] = left;
+                dst[1] = right;
+            }
+            _ => {
+                let shared = in_ch.min(out_ch);
+                dst[..shared].copy_from_slice(&src[..shared]);
+                dst[shared..].fill(0.0);
+            }
+        }
+    }
+}
+
+/// Non-interleaved block with one contiguous run of `frames` samples per channel.
+///
+/// The storage is allocated once up front; `set_layout` only ever shrinks the view into it, so
+/// nothing here allocates on the audio thread.
+pub struct Planar {
+    data: Vec<f32>,
+    frames: usize,
+    channels: usize,
+}
+
+impl Planar {
+    pub fn with_capacity(samples: usize) -> Self {
+        Self { data: vec![0.0; samples], frames: 0, channels: 0 }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Panics if `frames * channels` exceeds the capacity.
+    pub fn set_layout(&mut self, frames: usize, channels: usize) {
+        assert!(frames * channels <= self.data.len(), "planar block exceeds preallocated capacity");
+        self.frames = frames;
+        self.channels = channels;
+    }
+
+    pub fn iter_channels(&self) -> impl Iterator<Item = &[f32]> {
+        self.data[..self.frames * self.channels].chunks_exact(self.frames.max(1))
+    }
+
+    pub fn iter_channels_mut(&mut self) -> impl Iterator<Item = &mut [f32]> {
+        self.data[..self.frames * self.channels].chunks_exact_mut(self.frames.max(1))
+    }
+
+    /// Splits the current number of frames of `src`, starting at `start`, into per-channel runs.
+    pub fn deinterleave(&mut self, src: &Interleaved, start: usize) {
+        debug_assert_eq!(self.channels, src.channels());
+        let channels = self.channels;
+        for (i, run) in self.iter_channels_mut().enumerate() {
+            for (n, sample) in run.iter_mut().enumerate() {
+                *sample = src.samples()[(start + n) * channels + i];
+            }
+        }
+    }
+
+    /// Writes the per-channel runs back into `dst`, starting at frame `start`.
+    pub fn interleave(&self, dst: &mut InterleavedMut, start: usize) {
+        debug_assert_eq!(self.channels, dst.channels());
+        let channels = self.channels;
+        let samples = dst.samples_mut();
+        for (i, run) in self.iter_channels().enumerate() {
+            for (n, sample) in run.iter().enumerate() {
+                samples[(start + n) * channels + i] = *sample;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Remixes `input` (interleaved, `in_ch` channels) into a fresh `out_ch`-channel block.
+    fn remixed(input: &[f32], in_ch: usize, out_ch: usize) -> Vec<f32> {
+        let frames = input.len() / in_ch;
+        let mut output = vec![f32::NAN; frames * out_ch];
+        let src = unsafe { Interleaved::from_raw(input.as_ptr(), frames, in_ch) };
+        let mut dst = unsafe { InterleavedMut::from_raw(output.as_mut_ptr(), frames, out_ch) };
+        remix(&src, &mut dst);
+        output
+    }
+
+    #[test]
+    fn mono_to_stereo_duplicates() {
+        assert_eq!(remixed(&[0.25, -0.5], 1, 2), [0.25, 0.25, -0.5, -0.5]);
+    }
+
+    #[test]
+    fn stereo_to_mono_averages() {
+        assert_eq!(remixed(&[0.25, 0.75, -1.0, 0.5], 2, 1), [0.5, -0.25]);
+    }
+
+    #[test]
+    fn stereo_to_surround_fills_front_pair() {
+        let out = remixed(&[0.25, -0.5, 1.0, 0.75], 2, 6);
+        assert_eq!(out, [0.25, -0.5, 0.0, 0.0, 0.0, 0.0, 1.0, 0.75, 0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn surround_folds_down_to_stereo() {
+        let g = FRAC_1_SQRT_2;
+        // FL, FR, C, LFE, SL, SR: the LFE is dropped, centre and surrounds come in at -3 dB.
+        let out = remixed(&[1.0, 0.5, 0.25, 8.0, 0.125, 0.0625], 6, 2);
+        assert_eq!(out, [1.0 + 0.25 * g + 0.125 * g, 0.5 + 0.25 * g + 0.0625 * g]);
+
+        // 7.1 adds a back pair on top.
+        let out = remixed(&[1.0, 0.5, 0.25, 8.0, 0.125, 0.0625, 0.5, 0.25], 8, 2);
+        let (left, right) = (1.0 + (0.25 + 0.125 + 0.5) * g, 0.5 + (0.25 + 0.0625 + 0.25) * g);
+        assert!((out[0] - left).abs() < 1e-6 && (out[1] - right).abs() < 1e-6, "{out:?}");
+    }
+
+    #[test]
+    fn eight_channels_pass_through_or_average() {
+        let input: Vec<f32> = (0..16).map(|n| n as f32).collect();
+        assert_eq!(remixed(&input, 8, 8), input);
+        assert_eq!(remixed(&input, 8, 1), [3.5, 11.5]);
+        let out = remixed(&input[..8], 8, 6);
+        assert_eq!(out, [0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(remixed(&[0.5], 1, 8), [0.5; 8]);
+    }
+
+    #[test]
+    fn missing_input_silences_output() {
+        let mut output = [1.0f32; 4];
+        let mut dst = unsafe { InterleavedMut::from_raw(output.as_mut_ptr(), 2, 2) };
+        remix(&Interleaved::empty(2), &mut dst);
+        assert_eq!(output, [0.0; 4]);
+    }
+
+    #[test]
+    fn planar_round_trip_from_offset() {
+        let input: Vec<f32> = (0..30).map(|n| n as f32).collect();
+        let src = unsafe { Interleaved::from_raw(input.as_ptr(), 10, 3) };
+        let mut planar = Planar::with_capacity(32);
+        planar.set_layout(4, 3);
+        planar.deinterleave(&src, 5);
+        let runs: Vec<&[f32]> = planar.iter_channels().collect();
+        assert_eq!(runs, [&[15.0, 18.0, 21.0, 24.0][..], &[16.0, 19.0, 22.0, 25.0], &[17.0, 20.0, 23.0, 26.0]]);
+
+        let mut output = vec![-1.0f32; 30];
+        let mut dst = unsafe { InterleavedMut::from_raw(output.as_mut_ptr(), 10, 3) };
+        planar.interleave(&mut dst, 5);
+        // Only the frames the planar block covers are written.
+        assert!(output[..15].iter().chain(&output[27..]).all(|&x| x == -1.0));
+        assert_eq!(output[15..27], input[15..27]);
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds preallocated capacity")]
+    fn planar_layout_is_bounded_by_capacity() {
+        Planar::with_capacity(16).set_layout(9, 2);
+    }
+}
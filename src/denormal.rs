@@ -0,0 +1,71 @@
+//! Flush-to-zero protection for the audio thread.
+//!
+//! Feedback paths that decay towards silence end up in the denormal range, where x86 and ARM
+//! cores fall back to slow microcode. Flushing them to zero for the duration of a process call
+//! keeps the CPU cost flat without adding DC offsets to every recursive filter.
+
+/// Enables flush-to-zero (and denormals-are-zero where available) until dropped, then restores
+/// whatever mode the host had set.
+pub struct DenormalGuard {
+    #[cfg_attr(not(any(target_arch = "x86_64", target_arch = "aarch64")), allow(dead_code))]
+    previous: u64,
+}
+
+#[cfg(target_arch = "x86_64")]
+mod mode {
+    use std::arch::asm;
+
+    const FLUSH_TO_ZERO: u64 = 1 << 15;
+    const DENORMALS_ARE_ZERO: u64 = 1 << 6;
+    pub const FLAGS: u64 = FLUSH_TO_ZERO | DENORMALS_ARE_ZERO;
+
+    pub fn get() -> u64 {
+        let mut csr: u32 = 0;
+        unsafe { asm!("stmxcsr [{}]", in(reg) &mut csr, options(nostack, preserves_flags)) };
+        csr as u64
+    }
+
+    pub fn set(value: u64) {
+        let csr = value as u32;
+        unsafe { asm!("ldmxcsr [{}]", in(reg) &csr, options(nostack, readonly, preserves_flags)) };
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod mode {
+    use std::arch::asm;
+
+    // FPCR.FZ flushes both denormal inputs and outputs, so there's no separate DAZ bit.
+    pub const FLAGS: u64 = 1 << 24;
+
+    pub fn get() -> u64 {
+        let fpcr: u64;
+        unsafe { asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack, preserves_flags)) };
+        fpcr
+    }
+
+    pub fn set(value: u64) {
+        unsafe { asm!("msr fpcr, {}", in(reg) value, options(nomem, nostack, preserves_flags)) };
+    }
+}
+
+impl DenormalGuard {
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    pub fn enable() -> Self {
+        let previous = mode::get();
+        mode::set(previous | mode::FLAGS);
+        Self { previous }
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    pub fn enable() -> Self {
+        Self { previous: 0 }
+    }
+}
+
+impl Drop for DenormalGuard {
+    fn drop(&mut self) {
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        mode::set(self.previous);
+    }
+}
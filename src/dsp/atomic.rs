@@ -0,0 +1,79 @@
+//! Lock-free `f32` storage for values `process` publishes and the GUI thread polls.
+//!
+//! Every value stands on its own, so relaxed ordering is enough: a GUI frame that sees one bin a
+//! block older than its neighbour draws nothing wrong.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// `f32` kept as its bit pattern in an `AtomicU32`.
+#[derive(Debug, Default)]
+pub struct AtomicF32(AtomicU32);
+
+impl AtomicF32 {
+    pub const fn new(value: f32) -> Self {
+        Self(AtomicU32::new(value.to_bits()))
+    }
+
+    #[inline]
+    pub fn load(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    #[inline]
+    pub fn store(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn swap(&self, value: f32) -> f32 {
+        f32::from_bits(self.0.swap(value.to_bits(), Ordering::Relaxed))
+    }
+
+    /// Lowers the value to `value` if that's smaller, without losing a concurrent `store` or `swap`.
+    #[inline]
+    pub fn fetch_min(&self, value: f32) {
+        let _ = self.0.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            (value < f32::from_bits(bits)).then_some(value.to_bits())
+        });
+    }
+}
+
+/// Fixed-length run of `AtomicF32`s for spectra and other curves, allocated once in `new`.
+pub struct AtomicBuffer {
+    values: Box<[AtomicF32]>,
+}
+
+impl AtomicBuffer {
+    pub fn new(len: usize) -> Self {
+        Self { values: (0..len).map(|_| AtomicF32::default()).collect() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    #[inline]
+    pub fn load(&self, index: usize) -> f32 {
+        self.values[index].load()
+    }
+
+    #[inline]
+    pub fn store(&self, index: usize, value: f32) {
+        self.values[index].store(value);
+    }
+
+    /// Overwrites the start of the buffer with `values`.
+    pub fn store_from(&self, values: &[f32]) {
+        for (cell, &value) in self.values.iter().zip(values) {
+            cell.store(value);
+        }
+    }
+
+    pub fn fill(&self, value: f32) {
+        self.values.iter().for_each(|cell| cell.store(value));
+    }
+}
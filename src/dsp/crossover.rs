@@ -0,0 +1,78 @@
+//! Linkwitz-Riley crossovers for multiband processing.
+
+use crate::dsp::filter::BiquadFilter;
+
+const BUTTERWORTH_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Most bands a `Crossover` can split into.
+pub const MAX_BANDS: usize = 4;
+
+/// 4th-order Linkwitz-Riley two-way split: cascaded Butterworth pairs whose outputs are in phase
+/// and sum to a flat magnitude response.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LinkwitzRiley {
+    lowpass: [BiquadFilter; 2],
+    highpass: [BiquadFilter; 2],
+}
+
+impl LinkwitzRiley {
+    pub fn setup(&mut self, freq: f32, samplerate: f32) {
+        for (lp, hp) in self.lowpass.iter_mut().zip(self.highpass.iter_mut()) {
+            lp.setup_lowpass(freq, samplerate, BUTTERWORTH_Q);
+            hp.setup_highpass(freq, samplerate, BUTTERWORTH_Q);
+        }
+    }
+
+    /// Returns `(low, high)`.
+    #[inline]
+    pub fn split(&mut self, input: f32) -> (f32, f32) {
+        let low = self.lowpass.iter_mut().fold(input, |x, f| f.process(x));
+        let high = self.highpass.iter_mut().fold(input, |x, f| f.process(x));
+        (low, high)
+    }
+}
+
+/// Splits one channel into up to `MAX_BANDS` bands that sum back to an allpass response.
+///
+/// The bands are peeled off from the bottom one crossover at a time. Each lower band then runs
+/// through allpasses matching the crossovers it skipped, so every band sees the same phase shift
+/// and summing them doesn't comb.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Crossover {
+    splits: [LinkwitzRiley; MAX_BANDS - 1],
+    compensation: [[BiquadFilter; MAX_BANDS - 1]; MAX_BANDS - 1],
+    bands: usize,
+}
+
+impl Crossover {
+    /// `freqs` are the ascending crossover frequencies, one fewer than the number of bands.
+    pub fn setup(&mut self, freqs: &[f32], samplerate: f32) {
+        let splits = freqs.len().min(MAX_BANDS - 1);
+        self.bands = splits + 1;
+        for (i, &freq) in freqs[..splits].iter().enumerate() {
+            self.splits[i].setup(freq, samplerate);
+            // The LR4 low/high sum is a second order allpass at the crossover frequency.
+            for band in 0..i {
+                self.compensation[band][i].setup_allpass(freq, samplerate, BUTTERWORTH_Q);
+            }
+        }
+    }
+
+    pub fn bands(&self) -> usize {
+        self.bands
+    }
+
+    /// Writes the first `bands()` entries of `out`, lowest band first.
+    #[inline]
+    pub fn process(&mut self, input: f32, out: &mut [f32; MAX_BANDS]) {
+        let splits = self.bands.saturating_sub(1);
+        let mut rest = input;
+        let bands = self.splits[..splits].iter_mut().zip(&mut self.compensation).zip(out.iter_mut());
+        for (i, ((split, allpasses), band)) in bands.enumerate() {
+            let (low, high) = split.split(rest);
+            *band = allpasses[i + 1..splits].iter_mut().fold(low, |x, f| f.process(x));
+            rest = high;
+        }
+        out[splits] = rest;
+    }
+}
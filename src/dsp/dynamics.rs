@@ -0,0 +1,185 @@
+//! Shared pieces for compressors, limiters and gates.
+
+use crate::audio_buffer::Interleaved;
+use crate::dsp::atomic::AtomicF32;
+
+#[inline]
+pub fn db_to_gain(db: f32) -> f32 {
+    10.0f32.powf(db * 0.05)
+}
+
+/// Clamped at -200 dB so silence doesn't produce `-inf`.
+#[inline]
+pub fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-10).log10()
+}
+
+/// Static compression curve: the output level in dB for an input `level` in dB.
+///
+/// `knee` is the width in dB of the quadratic transition around the threshold; 0 gives a hard knee.
+#[inline]
+pub fn compress_db(level: f32, threshold: f32, ratio: f32, knee: f32) -> f32 {
+    let over = level - threshold;
+    let slope = 1.0 / ratio.max(1.0) - 1.0;
+    if 2.0 * over <= -knee {
+        level
+    } else if 2.0 * over >= knee {
+        level + slope * over
+    } else {
+        let x = over + 0.5 * knee;
+        level + slope * x * x / (2.0 * knee)
+    }
+}
+
+/// One-pole coefficient that gets within 1/e of a step after `ms` milliseconds.
+#[inline]
+pub fn time_coefficient(ms: f32, samplerate: f32) -> f32 {
+    if ms <= 0.0 {
+        0.0
+    } else {
+        (-1000.0 / (ms * samplerate)).exp()
+    }
+}
+
+/// Attack/release smoothing of a gain in dB.
+///
+/// Moves towards lower (more attenuated) targets with the attack time and back up with the release
+/// time, so the detector reacts quickly and lets go smoothly.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ballistics {
+    attack: f32,
+    release: f32,
+    state: f32,
+}
+
+impl Ballistics {
+    pub fn set_times(&mut self, attack_ms: f32, release_ms: f32, samplerate: f32) {
+        self.attack = time_coefficient(attack_ms, samplerate);
+        self.release = time_coefficient(release_ms, samplerate);
+    }
+
+    #[inline]
+    pub fn process(&mut self, target: f32) -> f32 {
+        let coeff = if target < self.state { self.attack } else { self.release };
+        self.state = target + coeff * (self.state - target);
+        self.state
+    }
+
+    pub fn value(&self) -> f32 {
+        self.state
+    }
+
+    pub fn reset(&mut self, value: f32) {
+        self.state = value;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Detection {
+    Peak,
+    Rms,
+}
+
+/// Level detector returning dB, either instantaneous peak or RMS over a short window.
+#[derive(Clone, Copy, Debug)]
+pub struct LevelDetector {
+    mode: Detection,
+    rms_coeff: f32,
+    mean_square: f32,
+}
+
+impl LevelDetector {
+    pub fn new(mode: Detection) -> Self {
+        Self { mode, rms_coeff: 0.0, mean_square: 0.0 }
+    }
+
+    pub fn set_mode(&mut self, mode: Detection) {
+        self.mode = mode;
+    }
+
+    pub fn set_rms_window(&mut self, ms: f32, samplerate: f32) {
+        self.rms_coeff = time_coefficient(ms, samplerate);
+    }
+
+    /// `input` is the linear detector signal, usually the largest magnitude across channels.
+    #[inline]
+    pub fn process(&mut self, input: f32) -> f32 {
+        match self.mode {
+            Detection::Peak => gain_to_db(input.abs()),
+            Detection::Rms => {
+                self.mean_square = input * input + self.rms_coeff * (self.mean_square - input * input);
+                // mean square -> dB is 10 log10, i.e. half of gain_to_db
+                0.5 * gain_to_db(self.mean_square)
+            }
+        }
+    }
+}
+
+/// What the detector of a side-chain capable effect listens to.
+///
+/// Keying lets e.g. dialogue duck the music bus, or a kick open a gate on another one. Without an
+/// active send the effect falls back to its own input rather than going quiet.
+pub struct SidechainKey<'a> {
+    sidechain: Option<Interleaved<'a>>,
+}
+
+impl<'a> SidechainKey<'a> {
+    /// `keyed` is the effect's "Side-chain Mode" setting.
+    pub fn new(sidechain: &Interleaved<'a>, keyed: bool) -> Self {
+        let active = keyed && sidechain.channels() > 0;
+        Self { sidechain: active.then_some(*sidechain) }
+    }
+
+    /// Largest magnitude across the key's channels in frame `n`; `own` is that frame of the
+    /// effect's input.
+    #[inline]
+    pub fn peak(&self, n: usize, own: &[f32]) -> f32 {
+        let key = match &self.sidechain {
+            Some(sidechain) => sidechain.frame(n),
+            None => own,
+        };
+        key.iter().fold(0.0f32, |peak, x| peak.max(x.abs()))
+    }
+}
+
+/// Gain reduction for a "GainReduction" float buffer, in dB at or below 0 so meters can draw it
+/// straight down.
+///
+/// Reports the deepest reduction since it was last taken, so a duck shorter than the GUI's refresh
+/// still shows, then restarts from the current reduction so a steady one reads steadily. `update`
+/// and `take` may run concurrently on the audio and GUI threads.
+#[derive(Debug, Default)]
+pub struct GainReductionMeter {
+    deepest: AtomicF32,
+    current: AtomicF32,
+}
+
+impl GainReductionMeter {
+    /// Folds in one block: the deepest reduction within it and where it ended up.
+    pub fn update(&self, deepest: f32, current: f32) {
+        self.current.store(current);
+        self.deepest.fetch_min(deepest);
+    }
+
+    pub fn take(&self) -> f32 {
+        self.deepest.swap(self.current.load())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meter_holds_the_deepest_reduction_until_read() {
+        let meter = GainReductionMeter::default();
+        meter.update(-12.0, -3.0);
+        meter.update(-6.0, -2.0);
+        assert_eq!(meter.take(), -12.0);
+        // A read restarts from where the last block ended rather than from 0.
+        assert_eq!(meter.take(), -2.0);
+        meter.update(-1.0, 0.0);
+        assert_eq!(meter.take(), -2.0);
+        assert_eq!(meter.take(), 0.0);
+    }
+}
@@ -0,0 +1,321 @@
+//! `BiquadFilter` and `StateVariableFilter`, ported from `AudioPluginUtil`.
+
+use std::f32::consts::PI;
+
+/// Direct form II biquad.
+///
+/// The `setup_*` methods only replace the coefficients, so they can be called every block while
+/// the filter keeps running without clicks. Coefficient formulae are from Robert Bristow-Johnson's
+/// EQ cookbook: http://www.musicdsp.org/files/Audio-EQ-Cookbook.txt
+#[derive(Clone, Copy, Debug)]
+pub struct BiquadFilter {
+    a1: f32,
+    a2: f32,
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Default for BiquadFilter {
+    /// A filter that passes its input through unchanged.
+    fn default() -> Self {
+        Self { a1: 0.0, a2: 0.0, b0: 1.0, b1: 0.0, b2: 0.0, z1: 0.0, z2: 0.0 }
+    }
+}
+
+impl BiquadFilter {
+    #[inline]
+    pub fn process(&mut self, input: f32) -> f32 {
+        let iir = input - self.a1 * self.z1 - self.a2 * self.z2;
+        let fir = self.b0 * iir + self.b1 * self.z1 + self.b2 * self.z2;
+        self.z2 = self.z1;
+        self.z1 = iir;
+        fir
+    }
+
+    /// Clears the filter state but keeps the coefficients.
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    pub fn setup_peaking(&mut self, cutoff: f32, samplerate: f32, gain: f32, q: f32) {
+        let (w0, alpha) = omega_alpha(cutoff, samplerate, q);
+        let a = shelf_amplitude(gain);
+        let cos = w0.cos();
+        self.set(1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a, 1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a);
+    }
+
+    pub fn setup_low_shelf(&mut self, cutoff: f32, samplerate: f32, gain: f32, q: f32) {
+        let (w0, alpha) = omega_alpha(cutoff, samplerate, q);
+        let a = shelf_amplitude(gain);
+        let (cos, sqrt_a) = (w0.cos(), a.sqrt());
+        self.set(
+            a * ((a + 1.0) - (a - 1.0) * cos + 2.0 * sqrt_a * alpha),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - 2.0 * sqrt_a * alpha),
+            (a + 1.0) + (a - 1.0) * cos + 2.0 * sqrt_a * alpha,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - 2.0 * sqrt_a * alpha,
+        );
+    }
+
+    pub fn setup_high_shelf(&mut self, cutoff: f32, samplerate: f32, gain: f32, q: f32) {
+        let (w0, alpha) = omega_alpha(cutoff, samplerate, q);
+        let a = shelf_amplitude(gain);
+        let (cos, sqrt_a) = (w0.cos(), a.sqrt());
+        self.set(
+            a * ((a + 1.0) + (a - 1.0) * cos + 2.0 * sqrt_a * alpha),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - 2.0 * sqrt_a * alpha),
+            (a + 1.0) - (a - 1.0) * cos + 2.0 * sqrt_a * alpha,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - 2.0 * sqrt_a * alpha,
+        );
+    }
+
+    pub fn setup_lowpass(&mut self, cutoff: f32, samplerate: f32, q: f32) {
+        let (w0, alpha) = omega_alpha(cutoff, samplerate, q);
+        let cos = w0.cos();
+        self.set((1.0 - cos) * 0.5, 1.0 - cos, (1.0 - cos) * 0.5, 1.0 + alpha, -2.0 * cos, 1.0 - alpha);
+    }
+
+    pub fn setup_highpass(&mut self, cutoff: f32, samplerate: f32, q: f32) {
+        let (w0, alpha) = omega_alpha(cutoff, samplerate, q);
+        let cos = w0.cos();
+        self.set((1.0 + cos) * 0.5, -(1.0 + cos), (1.0 + cos) * 0.5, 1.0 + alpha, -2.0 * cos, 1.0 - alpha);
+    }
+
+    /// Band-pass with a constant 0 dB peak gain.
+    pub fn setup_bandpass(&mut self, cutoff: f32, samplerate: f32, q: f32) {
+        let (w0, alpha) = omega_alpha(cutoff, samplerate, q);
+        self.set(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * w0.cos(), 1.0 - alpha);
+    }
+
+    pub fn setup_notch(&mut self, cutoff: f32, samplerate: f32, q: f32) {
+        let (w0, alpha) = omega_alpha(cutoff, samplerate, q);
+        let cos = w0.cos();
+        self.set(1.0, -2.0 * cos, 1.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha);
+    }
+
+    pub fn setup_allpass(&mut self, cutoff: f32, samplerate: f32, q: f32) {
+        let (w0, alpha) = omega_alpha(cutoff, samplerate, q);
+        let cos = w0.cos();
+        self.set(1.0 - alpha, -2.0 * cos, 1.0 + alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha);
+    }
+
+    /// Raw coefficients for designs the cookbook doesn't cover; normalized by `a0` here.
+    pub fn set_coefficients(&mut self, b: [f32; 3], a: [f32; 3]) {
+        self.set(b[0], b[1], b[2], a[0], a[1], a[2]);
+    }
+
+    /// Takes over the coefficients of `other` while keeping this filter's state.
+    pub fn copy_coefficients(&mut self, other: &BiquadFilter) {
+        *self = BiquadFilter { z1: self.z1, z2: self.z2, ..*other };
+    }
+
+    /// Linear magnitude of the frequency response at `freq`, for drawing response curves.
+    pub fn magnitude(&self, freq: f32, samplerate: f32) -> f32 {
+        let w = 2.0 * std::f64::consts::PI * freq as f64 / samplerate as f64;
+        let (c1, s1, c2, s2) = (w.cos(), w.sin(), (2.0 * w).cos(), (2.0 * w).sin());
+        let (b0, b1, b2) = (self.b0 as f64, self.b1 as f64, self.b2 as f64);
+        let (a1, a2) = (self.a1 as f64, self.a2 as f64);
+        let num = (b0 + b1 * c1 + b2 * c2).hypot(b1 * s1 + b2 * s2);
+        let den = (1.0 + a1 * c1 + a2 * c2).hypot(a1 * s1 + a2 * s2);
+        (num / den) as f32
+    }
+
+    fn set(&mut self, b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) {
+        let inv_a0 = 1.0 / a0;
+        self.b0 = b0 * inv_a0;
+        self.b1 = b1 * inv_a0;
+        self.b2 = b2 * inv_a0;
+        self.a1 = a1 * inv_a0;
+        self.a2 = a2 * inv_a0;
+    }
+}
+
+fn omega_alpha(cutoff: f32, samplerate: f32, q: f32) -> (f32, f32) {
+    let w0 = 2.0 * PI * cutoff / samplerate;
+    (w0, w0.sin() / (2.0 * q))
+}
+
+/// `gain` is in dB; the cookbook's A is the square root of the linear gain.
+fn shelf_amplitude(gain: f32) -> f32 {
+    10.0f32.powf(gain * 0.025)
+}
+
+/// Chamberlin state variable filter, run twice per sample to stay stable up to higher cutoffs.
+///
+/// Unlike the reference implementation, the input isn't scaled by `bandwidth`, so the low-pass
+/// output has unity gain in the passband regardless of resonance.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StateVariableFilter {
+    pub cutoff: f32,
+    pub bandwidth: f32,
+    lpf: f32,
+    bpf: f32,
+}
+
+impl StateVariableFilter {
+    /// Sets `cutoff` and `bandwidth` from a frequency in Hz and a resonance Q.
+    pub fn setup(&mut self, cutoff: f32, samplerate: f32, q: f32) {
+        // Half the sample period, since the filter is stepped twice per sample.
+        self.cutoff = 2.0 * (PI * cutoff / (2.0 * samplerate)).sin();
+        self.bandwidth = 1.0 / q;
+    }
+
+    #[inline]
+    pub fn process_hpf(&mut self, input: f32) -> f32 {
+        self.lpf += self.cutoff * self.bpf;
+        let hpf = input - self.bandwidth * self.bpf - self.lpf;
+        self.bpf += self.cutoff * hpf;
+
+        self.lpf += self.cutoff * self.bpf;
+        let hpf = input - self.bandwidth * self.bpf - self.lpf;
+        self.bpf += self.cutoff * hpf;
+
+        hpf
+    }
+
+    #[inline]
+    pub fn process_bpf(&mut self, input: f32) -> f32 {
+        self.process_hpf(input);
+        self.bpf
+    }
+
+    #[inline]
+    pub fn process_lpf(&mut self, input: f32) -> f32 {
+        self.process_hpf(input);
+        self.lpf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLERATE: f32 = 48000.0;
+    const CUTOFF: f32 = 1000.0;
+    const Q: f32 = 2.0;
+    const GAIN: f32 = 9.0;
+
+    const KINDS: [&str; 8] =
+        ["peaking", "low_shelf", "high_shelf", "lowpass", "highpass", "bandpass", "notch", "allpass"];
+
+    #[derive(Clone, Copy)]
+    struct C(f64, f64);
+
+    impl C {
+        fn mul(self, o: C) -> C {
+            C(self.0 * o.0 - self.1 * o.1, self.0 * o.1 + self.1 * o.0)
+        }
+        fn abs(self) -> f64 {
+            self.0.hypot(self.1)
+        }
+    }
+
+    /// Evaluates `b2 s^2 + b1 s + b0` at `s`.
+    fn poly(s: C, [b0, b1, b2]: [f64; 3]) -> C {
+        let s2 = s.mul(s);
+        C(b2 * s2.0 + b1 * s.0 + b0, b2 * s2.1 + b1 * s.1)
+    }
+
+    /// The cookbook's analog prototypes, as `(numerator, denominator)` coefficients in ascending
+    /// powers of the normalized `s`.
+    fn prototype(kind: &str) -> ([f64; 3], [f64; 3]) {
+        let q = Q as f64;
+        let a = 10f64.powf(GAIN as f64 / 40.0);
+        let sa = a.sqrt();
+        match kind {
+            "peaking" => ([1.0, a / q, 1.0], [1.0, 1.0 / (a * q), 1.0]),
+            "low_shelf" => ([a * a, a * sa / q, a], [1.0, sa / q, a]),
+            "high_shelf" => ([a, a * sa / q, a * a], [a, sa / q, 1.0]),
+            "lowpass" => ([1.0, 0.0, 0.0], [1.0, 1.0 / q, 1.0]),
+            "highpass" => ([0.0, 0.0, 1.0], [1.0, 1.0 / q, 1.0]),
+            "bandpass" => ([0.0, 1.0 / q, 0.0], [1.0, 1.0 / q, 1.0]),
+            "notch" => ([1.0, 0.0, 1.0], [1.0, 1.0 / q, 1.0]),
+            "allpass" => ([1.0, -1.0 / q, 1.0], [1.0, 1.0 / q, 1.0]),
+            _ => unreachable!(),
+        }
+    }
+
+    /// The cookbook designs are bilinear transforms prewarped to the cutoff, so the digital
+    /// response at `freq` is the prototype's at `s = j tan(pi freq / fs) / tan(pi cutoff / fs)`.
+    fn expected(kind: &str, freq: f32) -> f32 {
+        let warp = |f: f32| (std::f64::consts::PI * f as f64 / SAMPLERATE as f64).tan();
+        let s = C(0.0, warp(freq) / warp(CUTOFF));
+        let (num, den) = prototype(kind);
+        (poly(s, num).abs() / poly(s, den).abs()) as f32
+    }
+
+    fn designed(kind: &str) -> BiquadFilter {
+        let mut filter = BiquadFilter::default();
+        match kind {
+            "peaking" => filter.setup_peaking(CUTOFF, SAMPLERATE, GAIN, Q),
+            "low_shelf" => filter.setup_low_shelf(CUTOFF, SAMPLERATE, GAIN, Q),
+            "high_shelf" => filter.setup_high_shelf(CUTOFF, SAMPLERATE, GAIN, Q),
+            "lowpass" => filter.setup_lowpass(CUTOFF, SAMPLERATE, Q),
+            "highpass" => filter.setup_highpass(CUTOFF, SAMPLERATE, Q),
+            "bandpass" => filter.setup_bandpass(CUTOFF, SAMPLERATE, Q),
+            "notch" => filter.setup_notch(CUTOFF, SAMPLERATE, Q),
+            "allpass" => filter.setup_allpass(CUTOFF, SAMPLERATE, Q),
+            _ => unreachable!(),
+        }
+        filter
+    }
+
+    fn assert_close(kind: &str, freq: f32, actual: f32, expected: f32, tolerance: f32) {
+        let error = (actual - expected).abs();
+        assert!(
+            error <= tolerance * expected.max(0.1),
+            "{kind} at {freq} Hz: {actual} vs analytical {expected}"
+        );
+    }
+
+    #[test]
+    fn magnitude_matches_cookbook() {
+        let freqs = [10.0, 100.0, 500.0, 900.0, CUTOFF, 1100.0, 2000.0, 8000.0, 20000.0];
+        for kind in KINDS {
+            let filter = designed(kind);
+            for freq in freqs {
+                assert_close(kind, freq, filter.magnitude(freq, SAMPLERATE), expected(kind, freq), 1e-3);
+            }
+        }
+
+        // Spot checks straight from the cookbook's definitions.
+        let a = shelf_amplitude(GAIN);
+        let at = |kind: &str| expected(kind, CUTOFF);
+        assert!((at("peaking") - a * a).abs() < 1e-4);
+        assert!((at("low_shelf") - a).abs() < 1e-4 && (at("high_shelf") - a).abs() < 1e-4);
+        assert!((at("lowpass") - Q).abs() < 1e-4 && (at("highpass") - Q).abs() < 1e-4);
+        assert!((at("bandpass") - 1.0).abs() < 1e-4 && at("notch") < 1e-4);
+        assert!((expected("allpass", 123.0) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sine_response_matches_cookbook() {
+        // Whole cycles per second, so correlating over one second picks out the tone exactly.
+        let len = SAMPLERATE as usize;
+        for kind in KINDS {
+            for freq in [50.0, 700.0, CUTOFF, 1500.0, 6000.0] {
+                let mut filter = designed(kind);
+                let w = 2.0 * std::f64::consts::PI * freq as f64 / SAMPLERATE as f64;
+                // Settle first so the transient doesn't count.
+                for n in 0..len {
+                    filter.process((w * n as f64).sin() as f32);
+                }
+                let (mut re, mut im) = (0.0f64, 0.0f64);
+                for n in len..2 * len {
+                    let y = filter.process((w * n as f64).sin() as f32) as f64;
+                    re += y * (w * n as f64).sin();
+                    im += y * (w * n as f64).cos();
+                }
+                let amplitude = (2.0 * re.hypot(im) / len as f64) as f32;
+                assert_close(kind, freq, amplitude, expected(kind, freq), 2e-3);
+            }
+        }
+    }
+}
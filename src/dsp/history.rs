@@ -0,0 +1,241 @@
+//! `HistoryBuffer`, ported from `AudioPluginUtil`: a fixed-length delay line that doubles as the
+//! capture buffer behind scopes and meters.
+
+/// Circular buffer of the most recent `len()` samples.
+///
+/// Allocates once in `new`; feeding and reading never allocate.
+pub struct HistoryBuffer {
+    data: Vec<f32>,
+    write_index: usize,
+}
+
+impl HistoryBuffer {
+    pub fn new(length: usize) -> Self {
+        assert!(length >= 4, "history buffer needs room for cubic interpolation");
+        Self { data: vec![0.0; length], write_index: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.data.fill(0.0);
+    }
+
+    #[inline]
+    pub fn feed(&mut self, sample: f32) {
+        let w = if self.write_index + 1 == self.data.len() { 0 } else { self.write_index + 1 };
+        self.data[w] = sample;
+        self.write_index = w;
+    }
+
+    /// Feeds every `stride`th sample of `buf`, i.e. one channel of an interleaved block.
+    pub fn feed_strided(&mut self, buf: &[f32], stride: usize) {
+        for &sample in buf.iter().step_by(stride.max(1)) {
+            self.feed(sample);
+        }
+    }
+
+    /// The sample fed `delay` samples ago; 0 is the most recent one.
+    #[inline]
+    pub fn read(&self, delay: usize) -> f32 {
+        let len = self.data.len();
+        self.data[(self.write_index + len - delay % len) % len]
+    }
+
+    /// Linearly interpolated read `delay` samples into the past.
+    #[inline]
+    pub fn read_linear(&self, delay: f32) -> f32 {
+        let (i, frac) = self.split_delay(delay);
+        let s0 = self.read(i);
+        let s1 = self.read(i + 1);
+        s0 + (s1 - s0) * frac
+    }
+
+    /// 4-point Hermite interpolated read `delay` samples into the past. Less than a sample back there
+    /// is no later sample to fit, so the newest one stands in for it.
+    #[inline]
+    pub fn read_cubic(&self, delay: f32) -> f32 {
+        let (i, t) = self.split_delay(delay);
+        let ym1 = if i == 0 { self.read(0) } else { self.read(i - 1) };
+        let (y0, y1, y2) = (self.read(i), self.read(i + 1), self.read(i + 2));
+        let c1 = 0.5 * (y1 - ym1);
+        let c2 = ym1 - 2.5 * y0 + 2.0 * y1 - 0.5 * y2;
+        let c3 = 0.5 * (y2 - ym1) + 1.5 * (y0 - y1);
+        ((c3 * t + c2) * t + c1) * t + y0
+    }
+
+    /// Splits a fractional delay into a whole-sample part and the remaining fraction, clamped so
+    /// every interpolator stays inside the buffer.
+    fn split_delay(&self, delay: f32) -> (usize, f32) {
+        let delay = delay.clamp(0.0, (self.data.len() - 3) as f32);
+        let i = delay as usize;
+        (i, delay - i as f32)
+    }
+
+    /// Resamples the last `source_len` samples, starting `offset` samples back, onto `buffer` with
+    /// the oldest sample first. The final slot receives the number of samples actually written, as
+    /// the reference implementation does for its GUI readers.
+    pub fn read_buffer(&self, buffer: &mut [f32], source_len: usize, offset: f32) {
+        let Some((count, target)) = buffer.split_last_mut() else {
+            return;
+        };
+        let len = self.data.len() as f32;
+        let speed = source_len as f32 / target.len().max(1) as f32;
+        let mut p = offset;
+        let mut written = 0;
+        for out in target.iter_mut().rev() {
+            if p >= len - 1.0 {
+                break;
+            }
+            *out = self.read_linear(p);
+            p += speed;
+            written += 1;
+        }
+        *count = written as f32;
+    }
+}
+
+/// First-order allpass (Thiran) interpolator for modulated delays.
+///
+/// Keeps the flat magnitude response linear interpolation lacks, at the cost of one sample of
+/// state, which is why it lives outside `HistoryBuffer`: each read tap needs its own.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AllpassReader {
+    previous: f32,
+}
+
+impl AllpassReader {
+    #[inline]
+    pub fn read(&mut self, buffer: &HistoryBuffer, delay: f32) -> f32 {
+        let (mut i, mut frac) = buffer.split_delay(delay);
+        // Keep the fraction away from zero, where the allpass coefficient approaches 1 and rings.
+        if frac < 0.1 && i > 0 {
+            i -= 1;
+            frac += 1.0;
+        }
+        let eta = (1.0 - frac) / (1.0 + frac);
+        let out = eta * buffer.read(i) + buffer.read(i + 1) - eta * self.previous;
+        self.previous = out;
+        out
+    }
+
+    pub fn reset(&mut self) {
+        self.previous = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A buffer of `len` samples holding the tail of `signal(n)` for `n` up to `fed - 1`.
+    fn filled(len: usize, fed: usize, signal: impl Fn(f64) -> f64) -> HistoryBuffer {
+        let mut history = HistoryBuffer::new(len);
+        (0..fed).for_each(|n| history.feed(signal(n as f64) as f32));
+        history
+    }
+
+    #[test]
+    fn interpolated_reads_of_a_ramp() {
+        // Both interpolators reproduce a straight line exactly, once cubic has four points to fit.
+        let history = filled(64, 100, |n| n);
+        for delay in [0.0, 0.5, 1.0, 3.25, 10.5, 40.75, 60.9] {
+            let expected = 99.0 - delay;
+            assert!((history.read_linear(delay) - expected).abs() < 1e-4, "linear at {delay}");
+            if delay >= 1.0 {
+                assert!((history.read_cubic(delay) - expected).abs() < 1e-4, "cubic at {delay}");
+            }
+        }
+        for delay in [0.25, 0.5, 0.75] {
+            let cubic = history.read_cubic(delay);
+            assert!((98.0..=99.0).contains(&cubic), "cubic at {delay}: {cubic}");
+        }
+    }
+
+    #[test]
+    fn interpolated_reads_of_a_sine() {
+        let omega = 0.06;
+        let history = filled(128, 300, |n| (omega * n).sin());
+        for delay in [1.3, 5.5, 17.7, 60.45, 100.9] {
+            let expected = (omega * (299.0 - delay as f64)).sin() as f32;
+            let linear = (history.read_linear(delay) - expected).abs();
+            let cubic = (history.read_cubic(delay) - expected).abs();
+            // Linear interpolation is off by up to omega^2 / 8 between samples; cubic by far less.
+            assert!(linear < 5e-4, "linear at {delay}: error {linear}");
+            assert!(cubic < 2e-5, "cubic at {delay}: error {cubic}");
+        }
+    }
+
+    /// Steady-state gain and phase lag of a Thiran read at `delay`, for a sine of `omega` rad/sample.
+    fn allpass_response(delay: f32, omega: f64) -> (f64, f64) {
+        let mut history = HistoryBuffer::new(64);
+        let mut reader = AllpassReader::default();
+        let (mut s, mut c) = (0.0, 0.0);
+        // Long enough for the allpass state to settle, then a whole number of periods.
+        let settle = 2000;
+        let periods = (20_000.0 * omega / std::f64::consts::TAU).floor();
+        let len = (periods * std::f64::consts::TAU / omega).round() as usize;
+        for n in 0..settle + len {
+            history.feed((omega * n as f64).sin() as f32);
+            let out = reader.read(&history, delay) as f64;
+            if n >= settle {
+                s += out * (omega * n as f64).sin();
+                c += out * (omega * n as f64).cos();
+            }
+        }
+        let magnitude = 2.0 * (s * s + c * c).sqrt() / len as f64;
+        (magnitude, (-c).atan2(s))
+    }
+
+    #[test]
+    fn allpass_reader_is_flat_with_the_requested_delay() {
+        for delay in [2.05, 3.4, 7.5, 12.95] {
+            for omega in [0.05, 0.5, 1.5, 2.5] {
+                let (magnitude, _) = allpass_response(delay, omega);
+                assert!((magnitude - 1.0).abs() < 2e-3, "{delay} at {omega} rad: gain {magnitude}");
+            }
+            // Group delay from the phase slope at low frequency, where Thiran is accurate.
+            let (omega, step) = (0.05, 0.005);
+            let (_, low) = allpass_response(delay, omega);
+            let (_, high) = allpass_response(delay, omega + step);
+            let group_delay = (high - low) / step;
+            assert!((group_delay - delay as f64).abs() < 0.01, "{delay}: group delay {group_delay}");
+        }
+    }
+
+    #[test]
+    fn delays_clamp_to_the_buffer() {
+        let history = filled(16, 40, |n| n);
+        assert_eq!(history.read_linear(0.0), 39.0);
+        assert_eq!(history.read_cubic(0.0), 39.0);
+        assert_eq!(history.read_linear(-5.0), 39.0);
+        // The longest delay every interpolator can serve is `len - 3`.
+        for delay in [13.0, 16.0, 1e9] {
+            assert_eq!(history.read_linear(delay), 26.0, "linear at {delay}");
+            assert_eq!(history.read_cubic(delay), 26.0, "cubic at {delay}");
+        }
+        let mut reader = AllpassReader::default();
+        for delay in [0.0, 16.0, 1e9] {
+            assert!(reader.read(&history, delay).is_finite(), "allpass at {delay}");
+        }
+    }
+
+    #[test]
+    fn read_buffer_is_oldest_first_and_counts_what_fit() {
+        let history = filled(16, 40, |n| n);
+        let mut buffer = [0.0; 9];
+        history.read_buffer(&mut buffer, 8, 0.0);
+        assert_eq!(buffer, [32.0, 33.0, 34.0, 35.0, 36.0, 37.0, 38.0, 39.0, 8.0]);
+
+        // Asking for more history than the buffer holds stops at its end.
+        let mut buffer = [-1.0; 9];
+        history.read_buffer(&mut buffer, 32, 0.0);
+        assert_eq!(buffer, [-1.0, -1.0, -1.0, -1.0, 27.0, 31.0, 35.0, 39.0, 4.0]);
+    }
+}
@@ -0,0 +1,12 @@
+//! Reusable DSP building blocks, ported from the reference `AudioPluginUtil`.
+
+pub mod atomic;
+pub mod crossover;
+pub mod dynamics;
+pub mod fft;
+pub mod filter;
+pub mod history;
+pub mod noise;
+pub mod oscillator;
+pub mod ring_buffer;
+pub mod true_peak;
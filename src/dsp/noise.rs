@@ -0,0 +1,254 @@
+//! `Random` and `NoiseGenerator`, ported from `AudioPluginUtil`, plus colored noise sources.
+//!
+//! Everything here is seedable and bit-for-bit reproducible, so rendered output can be compared
+//! against golden data.
+
+/// The reference implementation's linear congruential generator.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Random {
+    seed: u32,
+}
+
+impl Random {
+    pub fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    pub fn seed(&mut self, seed: u32) {
+        self.seed = seed;
+    }
+
+    #[inline]
+    pub fn get(&mut self) -> u32 {
+        self.seed = self.seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        self.seed ^ (self.seed >> 16)
+    }
+
+    /// Uniformly distributed in `[min, max]`, with 24 bits of resolution.
+    #[inline]
+    pub fn get_float(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * (self.get() & 0xFFFFFF) as f32 * (1.0 / 0xFFFFFF as f32)
+    }
+}
+
+/// Smoothed random walk: picks a new random target every `period` samples and ramps linearly
+/// towards it.
+#[derive(Clone, Copy, Debug)]
+pub struct NoiseGenerator {
+    level: f32,
+    delta: f32,
+    min: f32,
+    max: f32,
+    period: f32,
+    inv_period: f32,
+    samples_left: i32,
+}
+
+impl Default for NoiseGenerator {
+    fn default() -> Self {
+        Self { level: 0.0, delta: 0.0, min: 0.0, max: 1.0, period: 100.0, inv_period: 0.01, samples_left: 0 }
+    }
+}
+
+impl NoiseGenerator {
+    pub fn set_range(&mut self, min: f32, max: f32) {
+        self.min = min;
+        self.max = max;
+    }
+
+    pub fn set_period(&mut self, period: f32) {
+        self.period = period;
+        self.inv_period = 1.0 / period;
+    }
+
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    #[inline]
+    pub fn sample(&mut self, random: &mut Random) -> f32 {
+        self.samples_left -= 1;
+        if self.samples_left <= 0 {
+            self.samples_left = self.period as i32;
+            self.delta = (random.get_float(self.min, self.max) - self.level) * self.inv_period;
+        }
+        self.level += self.delta;
+        self.level
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoiseColor {
+    /// Flat spectrum.
+    White,
+    /// -3 dB per octave.
+    Pink,
+    /// -6 dB per octave.
+    Brown,
+    /// Sparse ±1 impulses at random positions, one per period; sounds smooth at a fraction of the
+    /// energy, which makes it popular for decorrelation and reverb tails.
+    Velvet,
+}
+
+/// White, pink, brown or velvet noise, scaled to roughly the same peak level of ±1.
+#[derive(Clone, Copy, Debug)]
+pub struct ColoredNoise {
+    color: NoiseColor,
+    random: Random,
+    pink: [f32; 7],
+    brown: f32,
+    velvet_period: u32,
+    velvet_position: u32,
+    velvet_sign: f32,
+    velvet_counter: u32,
+}
+
+impl ColoredNoise {
+    pub fn new(color: NoiseColor, seed: u32) -> Self {
+        Self {
+            color,
+            random: Random::new(seed),
+            pink: [0.0; 7],
+            brown: 0.0,
+            velvet_period: 20,
+            velvet_position: 0,
+            velvet_sign: 1.0,
+            velvet_counter: 0,
+        }
+    }
+
+    pub fn color(&self) -> NoiseColor {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: NoiseColor) {
+        self.color = color;
+    }
+
+    /// Restarts the sequence, so the same seed renders the same output again.
+    pub fn reseed(&mut self, seed: u32) {
+        *self = Self { velvet_period: self.velvet_period, ..Self::new(self.color, seed) };
+    }
+
+    /// Average number of velvet impulses per second.
+    pub fn set_velvet_density(&mut self, density: f32, samplerate: f32) {
+        self.velvet_period = (samplerate / density.max(1.0)).max(1.0) as u32;
+    }
+
+    #[inline]
+    pub fn sample(&mut self) -> f32 {
+        match self.color {
+            NoiseColor::White => self.white(),
+            NoiseColor::Pink => self.pink(),
+            NoiseColor::Brown => self.brown(),
+            NoiseColor::Velvet => self.velvet(),
+        }
+    }
+
+    #[inline]
+    fn white(&mut self) -> f32 {
+        self.random.get_float(-1.0, 1.0)
+    }
+
+    /// Paul Kellet's refined pinking filter.
+    fn pink(&mut self) -> f32 {
+        let white = self.white();
+        let b = &mut self.pink;
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.153852;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+        let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115926;
+        pink * 0.11
+    }
+
+    /// Leaky integration of white noise; the leak keeps it from wandering off to DC.
+    fn brown(&mut self) -> f32 {
+        let white = self.white();
+        self.brown = (self.brown + 0.02 * white) / 1.02;
+        self.brown * 3.5
+    }
+
+    fn velvet(&mut self) -> f32 {
+        if self.velvet_counter == 0 {
+            self.velvet_position = self.random.get() % self.velvet_period;
+            self.velvet_sign = if self.random.get() & 0x8000 != 0 { 1.0 } else { -1.0 };
+        }
+        let out = if self.velvet_counter == self.velvet_position { self.velvet_sign } else { 0.0 };
+        self.velvet_counter += 1;
+        if self.velvet_counter >= self.velvet_period {
+            self.velvet_counter = 0;
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u32 = 12345;
+
+    #[test]
+    fn random_matches_reference_lcg() {
+        let mut random = Random::new(SEED);
+        let values: Vec<u32> = (0..6).map(|_| random.get()).collect();
+        assert_eq!(values, [87628157, 71073519, 2332870938, 2726932724, 3908524367, 483022205]);
+    }
+
+    #[test]
+    fn colors_match_golden_output() {
+        let golden: [(NoiseColor, [f32; 8]); 3] = [
+            (
+                NoiseColor::White,
+                [
+                    -0.5539096, -0.52737623, -0.90011287, 0.0757128,
+                    0.9324132, 0.58073413, 0.89762425, -0.24916434,
+                ],
+            ),
+            (
+                NoiseColor::Pink,
+                [
+                    -0.100363106, -0.15466174, -0.25948703, -0.15507753,
+                    0.04955818, 0.099157475, 0.1992688, 0.065218195,
+                ],
+            ),
+            (
+                NoiseColor::Brown,
+                [
+                    -0.038013406, -0.073460534, -0.13379258, -0.12597322,
+                    -0.059514027, -0.018492782, 0.04347149, 0.025519593,
+                ],
+            ),
+        ];
+        for (color, expected) in golden {
+            let mut noise = ColoredNoise::new(color, SEED);
+            let first: Vec<f32> = (0..8).map(|_| noise.sample()).collect();
+            assert_eq!(first, expected, "{color:?}");
+
+            // Reseeding mid-stream starts the same sequence over.
+            noise.reseed(SEED);
+            let again: Vec<f32> = (0..8).map(|_| noise.sample()).collect();
+            assert_eq!(again, expected, "{color:?} after reseed");
+        }
+    }
+
+    #[test]
+    fn velvet_matches_golden_output() {
+        let mut noise = ColoredNoise::new(NoiseColor::Velvet, SEED);
+        noise.set_velvet_density(4800.0, 48000.0);
+        let impulses = |noise: &mut ColoredNoise| -> Vec<(usize, f32)> {
+            (0..30).map(|_| noise.sample()).enumerate().filter(|&(_, x)| x != 0.0).collect()
+        };
+        // One impulse in each period of ten samples.
+        let expected = [(7, -1.0), (18, 1.0), (27, -1.0)];
+        assert_eq!(impulses(&mut noise), expected);
+        // The density survives a reseed.
+        noise.sample();
+        noise.reseed(SEED);
+        assert_eq!(impulses(&mut noise), expected);
+    }
+}
@@ -0,0 +1,178 @@
+//! Lock-free single-producer/single-consumer ring, the Rust counterpart of `AudioPluginUtil`'s
+//! `RingBuffer`. Used to hand analysis data from `process` to `getfloatbuffer`.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Fixed-capacity SPSC queue of `Copy` values.
+///
+/// Exactly one thread may call `push` and exactly one other thread may call `pop`/`skip`; both
+/// only need `&self`, so the ring can live inside effect data shared by the two callbacks.
+pub struct RingBuffer<T> {
+    buffer: Box<[UnsafeCell<T>]>,
+    read_pos: AtomicUsize,
+    write_pos: AtomicUsize,
+}
+
+// The positions are only ever advanced by their own side, and a slot is never read and written
+// at the same time, so sharing across one producer and one consumer is sound.
+unsafe impl<T: Send> Sync for RingBuffer<T> {}
+
+impl<T: Copy + Default> RingBuffer<T> {
+    /// Holds up to `capacity` values.
+    pub fn new(capacity: usize) -> Self {
+        // One slot stays empty to tell "full" apart from "empty".
+        let buffer = (0..capacity + 1).map(|_| UnsafeCell::new(T::default())).collect();
+        Self { buffer, read_pos: AtomicUsize::new(0), write_pos: AtomicUsize::new(0) }
+    }
+}
+
+impl<T: Copy> RingBuffer<T> {
+    pub fn capacity(&self) -> usize {
+        self.buffer.len() - 1
+    }
+
+    fn next(&self, pos: usize) -> usize {
+        if pos + 1 == self.buffer.len() { 0 } else { pos + 1 }
+    }
+
+    /// Producer side. Returns `false` and drops `value` if the consumer has fallen behind.
+    pub fn push(&self, value: T) -> bool {
+        let w = self.write_pos.load(Ordering::Relaxed);
+        let next = self.next(w);
+        if next == self.read_pos.load(Ordering::Acquire) {
+            return false;
+        }
+        unsafe { *self.buffer[w].get() = value };
+        self.write_pos.store(next, Ordering::Release);
+        true
+    }
+
+    /// Consumer side.
+    pub fn pop(&self) -> Option<T> {
+        let r = self.read_pos.load(Ordering::Relaxed);
+        if r == self.write_pos.load(Ordering::Acquire) {
+            return None;
+        }
+        let value = unsafe { *self.buffer[r].get() };
+        self.read_pos.store(self.next(r), Ordering::Release);
+        Some(value)
+    }
+
+    /// Consumer side. The value `pop` would return next, left in place.
+    pub fn peek(&self) -> Option<T> {
+        let r = self.read_pos.load(Ordering::Relaxed);
+        if r == self.write_pos.load(Ordering::Acquire) {
+            return None;
+        }
+        Some(unsafe { *self.buffer[r].get() })
+    }
+
+    /// Consumer side. Discards up to `count` values, returning how many were dropped.
+    pub fn skip(&self, count: usize) -> usize {
+        let skipped = count.min(self.len());
+        let r = self.read_pos.load(Ordering::Relaxed);
+        self.read_pos.store((r + skipped) % self.buffer.len(), Ordering::Release);
+        skipped
+    }
+
+    /// Number of values waiting. The other side may be moving meanwhile, so this is a lower bound
+    /// on the consumer side and an upper bound on the producer side.
+    pub fn len(&self) -> usize {
+        let w = self.write_pos.load(Ordering::Acquire);
+        let r = self.read_pos.load(Ordering::Acquire);
+        if w >= r { w - r } else { w + self.buffer.len() - r }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_and_empty() {
+        let ring = RingBuffer::<u32>::new(3);
+        assert!(ring.is_empty());
+        assert_eq!((ring.pop(), ring.peek()), (None, None));
+
+        assert!(ring.push(1) && ring.push(2) && ring.push(3));
+        assert_eq!(ring.len(), ring.capacity());
+        // A full ring drops new values rather than overwriting unread ones.
+        assert!(!ring.push(4));
+        assert_eq!(ring.peek(), Some(1));
+
+        assert_eq!([ring.pop(), ring.pop(), ring.pop(), ring.pop()], [Some(1), Some(2), Some(3), None]);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn wraps_around() {
+        let ring = RingBuffer::<u32>::new(5);
+        let mut next_in = 0;
+        let mut next_out = 0;
+        // Uneven push and pop counts walk both positions around the storage many times.
+        for round in 0..100 {
+            for _ in 0..(round % 4 + 1) {
+                if ring.push(next_in) {
+                    next_in += 1;
+                }
+            }
+            assert_eq!(ring.len(), (next_in - next_out) as usize);
+            for _ in 0..(round % 3 + 1) {
+                if let Some(value) = ring.pop() {
+                    assert_eq!(value, next_out);
+                    next_out += 1;
+                }
+            }
+        }
+        assert!(next_in > 100);
+    }
+
+    #[test]
+    fn skip_stops_at_write_position() {
+        let ring = RingBuffer::<u32>::new(4);
+        for value in 0..3 {
+            ring.push(value);
+        }
+        assert_eq!(ring.skip(2), 2);
+        assert!(ring.push(3) && ring.push(4) && ring.push(5));
+        // Across the wrap, and no further than what was written.
+        assert_eq!(ring.skip(10), 4);
+        assert!(ring.is_empty());
+        assert!(ring.push(6));
+        assert_eq!(ring.pop(), Some(6));
+    }
+
+    #[test]
+    fn hands_values_across_threads_in_order() {
+        let ring = RingBuffer::<u32>::new(64);
+        const COUNT: u32 = 20_000;
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let mut value = 0;
+                while value < COUNT {
+                    if ring.push(value) {
+                        value += 1;
+                    } else {
+                        std::thread::yield_now();
+                    }
+                }
+            });
+            let mut expected = 0;
+            while expected < COUNT {
+                assert!(ring.len() <= ring.capacity());
+                match ring.pop() {
+                    Some(value) => {
+                        assert_eq!(value, expected);
+                        expected += 1;
+                    }
+                    None => std::thread::yield_now(),
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,458 @@
+use std::cell::UnsafeCell;
+use std::ffi::CStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::audio_buffer::{Interleaved, InterleavedMut, Planar};
+use crate::denormal::DenormalGuard;
+use crate::dsp::atomic::AtomicF32;
+use crate::realtime::AudioThreadGuard;
+use crate::unity_audio_dsp::{
+    UnityAudioEffectState, UnityAudioEffectState_Data, UnityAudioEffectStateFlags_IsSideChainTarget,
+    UnityAudioResult,
+};
+
+/// Channel count per-channel state and the planar scratch buffers are sized for (7.1).
+pub const MAX_CHANNELS: usize = 8;
+
+/// Block size assumed when the host is too old to report `dspbuffersize`.
+const DEFAULT_DSP_BUFFER_SIZE: usize = 1024;
+
+/// Host information for the block currently being processed.
+pub struct ProcessContext<'a> {
+    pub samplerate: u32,
+    /// Sample count at the start of this block, for syncing to the host clock.
+    pub currdsptick: u64,
+    /// Audio sent to this effect from another mixer group, laid out like the input block. Empty
+    /// unless the effect was declared with `UnityAudioEffectDefinitionFlags_IsSideChainTarget` and
+    /// something is actually sending to it.
+    pub sidechain: Interleaved<'a>,
+}
+
+impl ProcessContext<'_> {
+    /// # Safety
+    /// `state.sidechainbuffer` must be null or hold `frames * channels` samples for this block.
+    unsafe fn from_state(state: &UnityAudioEffectState_Data, frames: usize, channels: usize) -> Self {
+        let has_sidechain = state.flags & UnityAudioEffectStateFlags_IsSideChainTarget != 0;
+        let sidechain = if has_sidechain {
+            Interleaved::from_raw(state.sidechainbuffer, frames, channels)
+        } else {
+            Interleaved::empty(frames)
+        };
+
+        Self { samplerate: state.samplerate, currdsptick: state.currdsptick, sidechain }
+    }
+}
+
+/// A plugin instance driven by the generic callbacks below.
+///
+/// The callbacks take care of the raw pointers Unity hands us, so implementations only ever see
+/// typed views with the real input and output channel counts.
+///
+/// Unity calls `process` on the audio thread while `setfloatparameter`, `getfloatparameter` and
+/// `getfloatbuffer` come from the GUI thread at any time. The effect itself therefore only ever
+/// runs on the audio thread: parameter writes are queued by the adapter and delivered before the
+/// next block, and the GUI side reads nothing but `Shared`.
+pub trait Effect: Sized {
+    /// What `process` publishes for `get_float_buffer`: meters, spectra, captured audio. It's read
+    /// while `process` runs, so it is built from atomics and `RingBuffer`s. Effects with nothing
+    /// to show use `()`.
+    type Shared: Send + Sync;
+
+    /// Called from `create`. `state.samplerate` and `state.dspbuffersize` are valid here.
+    ///
+    /// Buffers sized from `state.samplerate` aren't grown if the host later runs faster; effects clamp
+    /// their delays and windows to what they allocated instead.
+    fn create(state: &UnityAudioEffectState_Data) -> Self;
+
+    /// Handed to the adapter once, right after `create`. The effect keeps its own reference to
+    /// publish into.
+    fn shared(&self) -> Arc<Self::Shared>;
+
+    /// Parameter storage, indexed the same way as the definitions passed to `declare_effect`.
+    /// The values found here after `create` are the initial ones.
+    fn parameters(&mut self) -> &mut [f32];
+
+    /// Called at the start of the next block for each parameter written through
+    /// `setfloatparameter` since the last one. Several writes to one parameter arrive as one call
+    /// with the latest value.
+    fn parameter_changed(&mut self, _index: usize) {}
+
+    /// When set, the adapter calls `process_planar` instead of `process`, with the block split
+    /// into one contiguous run per channel.
+    const DEINTERLEAVE: bool = false;
+
+    /// Generators still get whatever input buffer Unity passes, normally silence, and should ignore
+    /// it and fill `output` on their own.
+    fn process(&mut self, _context: &ProcessContext, _input: Interleaved, _output: InterleavedMut) {}
+
+    /// Planar counterpart of `process`. `output` starts out silent.
+    ///
+    /// Blocks larger than the scratch buffers preallocated from `dspbuffersize` arrive as several
+    /// consecutive calls, so implementations must not assume one call per Unity callback.
+    fn process_planar(&mut self, _context: &ProcessContext, _input: &Planar, _output: &mut Planar) {}
+
+    /// Fills `buffer` with the named analysis data. Unknown names leave the buffer untouched.
+    ///
+    /// Runs on the GUI thread, so it only sees what `process` published and the parameter values
+    /// as last set from script.
+    fn get_float_buffer(_shared: &Self::Shared, _parameters: &Parameters, _name: &CStr, _buffer: &mut [f32]) {}
+}
+
+/// Parameter values as set from the GUI thread, picked up by the audio thread before each block.
+pub struct Parameters {
+    values: Box<[AtomicF32]>,
+    /// One flag per parameter, raised by `set` and cleared when the effect has been told.
+    changed: Box<[AtomicBool]>,
+}
+
+impl Parameters {
+    fn new(initial: &[f32]) -> Self {
+        Self {
+            values: initial.iter().map(|&value| AtomicF32::new(value)).collect(),
+            changed: initial.iter().map(|_| AtomicBool::new(false)).collect(),
+        }
+    }
+
+    /// Current value of parameter `index`, or `None` past the end.
+    pub fn get(&self, index: usize) -> Option<f32> {
+        self.values.get(index).map(AtomicF32::load)
+    }
+
+    fn set(&self, index: usize, value: f32) -> bool {
+        let Some(cell) = self.values.get(index) else {
+            return false;
+        };
+        cell.store(value);
+        self.changed[index].store(true, Ordering::Release);
+        true
+    }
+
+    /// Delivers writes made since the last call to `effect`.
+    fn apply<E: Effect>(&self, effect: &mut E) {
+        for (index, changed) in self.changed.iter().enumerate() {
+            if changed.swap(false, Ordering::Acquire) {
+                if let Some(param) = effect.parameters().get_mut(index) {
+                    *param = self.values[index].load();
+                }
+                effect.parameter_changed(index);
+            }
+        }
+    }
+}
+
+/// What the callbacks store in `effectdata`.
+struct Instance<E: Effect> {
+    /// Only touched from `process_callback`, which Unity never runs concurrently with itself.
+    audio: UnsafeCell<AudioState<E>>,
+    parameters: Parameters,
+    shared: Arc<E::Shared>,
+}
+
+// The GUI thread only reaches `parameters` and `shared`, both of which are `Sync`; `audio` stays on
+// the audio thread.
+unsafe impl<E: Effect + Send> Sync for Instance<E> {}
+
+impl<E: Effect> Instance<E> {
+    fn new(state: &UnityAudioEffectState_Data) -> Self {
+        let mut effect = E::create(state);
+        let parameters = Parameters::new(effect.parameters());
+        let shared = effect.shared();
+        let planar = E::DEINTERLEAVE.then(|| {
+            let samples = dsp_buffer_size(state) * MAX_CHANNELS;
+            (Planar::with_capacity(samples), Planar::with_capacity(samples))
+        });
+
+        Self { audio: UnsafeCell::new(AudioState { effect, planar }), parameters, shared }
+    }
+}
+
+/// The effect plus the adapter's own scratch state, owned by the audio thread.
+struct AudioState<E> {
+    effect: E,
+    planar: Option<(Planar, Planar)>,
+}
+
+impl<E: Effect> AudioState<E> {
+    fn process(&mut self, context: &ProcessContext, input: Interleaved, mut output: InterleavedMut) {
+        let Some((planar_in, planar_out)) = &mut self.planar else {
+            self.effect.process(context, input, output);
+            return;
+        };
+
+        // Walk the block in chunks that fit the scratch buffers rather than allocating more.
+        let in_ch = input.channels();
+        let out_ch = output.channels();
+        let chunk = planar_in.capacity() / in_ch.max(out_ch).max(1);
+        let mut start = 0;
+        while start < output.frames() {
+            let frames = chunk.min(output.frames() - start);
+            planar_in.set_layout(frames, in_ch);
+            planar_in.deinterleave(&input, start);
+            planar_out.set_layout(frames, out_ch);
+            planar_out.iter_channels_mut().for_each(|run| run.fill(0.0));
+            let chunk_context = ProcessContext {
+                samplerate: context.samplerate,
+                currdsptick: context.currdsptick + start as u64,
+                sidechain: context.sidechain.slice_frames(start, frames),
+            };
+            self.effect.process_planar(&chunk_context, planar_in, planar_out);
+            planar_out.interleave(&mut output, start);
+            start += frames;
+        }
+    }
+}
+
+fn dsp_buffer_size(state: &UnityAudioEffectState_Data) -> usize {
+    let has_buffer_size = state.structsize as usize >= std::mem::size_of::<UnityAudioEffectState_Data>();
+    match state.dspbuffersize as usize {
+        size if has_buffer_size && size > 0 => size,
+        _ => DEFAULT_DSP_BUFFER_SIZE,
+    }
+}
+
+unity_dsp_callback!(
+    pub fn create_callback<E: Effect>(state: *mut UnityAudioEffectState) -> UnityAudioResult {
+        let effect_data = Box::new(Instance::<E>::new(unsafe { &(*state).data }));
+
+        // dropped in release_callback
+        unsafe {
+            (*state).data.effectdata = Box::leak(effect_data) as *mut Instance<E> as *mut ();
+        }
+
+        UnityAudioResult::Ok
+    }
+);
+
+unity_dsp_callback!(
+    pub fn release_callback<E: Effect>(state: *mut UnityAudioEffectState) -> UnityAudioResult {
+        unsafe {
+            let effect_data = (*state).data.effectdata as *mut Instance<E>;
+            drop(Box::from_raw(effect_data));
+        }
+
+        UnityAudioResult::Ok
+    }
+);
+
+unity_dsp_callback!(
+    pub fn set_float_parameter_callback<E: Effect>(
+        state: *mut UnityAudioEffectState,
+        index: i32,
+        value: f32,
+    ) -> UnityAudioResult {
+        let data: &Instance<E> = unsafe { (*state).get_effect_data() };
+
+        if index < 0 || !data.parameters.set(index as usize, value) {
+            return UnityAudioResult::ErrUnsupported;
+        }
+
+        UnityAudioResult::Ok
+    }
+);
+
+unity_dsp_callback!(
+    pub fn get_float_parameter_callback<E: Effect>(
+        state: *mut UnityAudioEffectState,
+        index: i32,
+        value: *mut f32,
+        value_str: *mut u8,
+    ) -> UnityAudioResult {
+        let data: &Instance<E> = unsafe { (*state).get_effect_data() };
+
+        let param = match data.parameters.get(index as usize) {
+            Some(param) if index >= 0 => param,
+            _ => return UnityAudioResult::ErrUnsupported,
+        };
+
+        if !value.is_null() {
+            unsafe {
+                *value = param;
+            }
+        }
+
+        if !value_str.is_null() {
+            unsafe {
+                *value_str = 0;
+            }
+        }
+
+        UnityAudioResult::Ok
+    }
+);
+
+unity_dsp_callback!(
+    pub fn get_float_buffer_callback<E: Effect>(
+        state: *mut UnityAudioEffectState,
+        name: *const u8,
+        buffer: *mut f32,
+        num_samples: i32,
+    ) -> UnityAudioResult {
+        if name.is_null() || buffer.is_null() || num_samples <= 0 {
+            return UnityAudioResult::Ok;
+        }
+
+        let data: &Instance<E> = unsafe { (*state).get_effect_data() };
+        let name = unsafe { CStr::from_ptr(name as *const _) };
+        let buffer = unsafe { std::slice::from_raw_parts_mut(buffer, num_samples as usize) };
+
+        E::get_float_buffer(&data.shared, &data.parameters, name, buffer);
+
+        UnityAudioResult::Ok
+    }
+);
+
+unity_dsp_callback!(
+    pub fn process_callback<E: Effect>(
+        state: *mut UnityAudioEffectState,
+        in_buffer: *const f32,
+        out_buffer: *mut f32,
+        length: u32,
+        in_channels: i32,
+        out_channels: i32,
+    ) -> UnityAudioResult {
+        let _audio_thread = AudioThreadGuard::enter();
+        let _denormals = DenormalGuard::enable();
+
+        let frames = length as usize;
+        let in_channels = in_channels.max(0) as usize;
+        let context = unsafe { ProcessContext::from_state(&(*state).data, frames, in_channels) };
+        let data: &Instance<E> = unsafe { (*state).get_effect_data() };
+        let audio = unsafe { &mut *data.audio.get() };
+
+        let input = unsafe { Interleaved::from_raw(in_buffer, frames, in_channels) };
+        let output = unsafe { InterleavedMut::from_raw(out_buffer, frames, out_channels.max(0) as usize) };
+
+        data.parameters.apply(&mut audio.effect);
+        audio.process(&context, input, output);
+
+        UnityAudioResult::Ok
+    }
+);
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+
+    /// Host state at `samplerate` as a current Unity version reports it to `create`.
+    pub fn state(samplerate: u32) -> UnityAudioEffectState_Data {
+        let mut state: UnityAudioEffectState_Data = unsafe { std::mem::zeroed() };
+        state.structsize = std::mem::size_of::<UnityAudioEffectState_Data>() as u32;
+        state.samplerate = samplerate;
+        state.dspbuffersize = DEFAULT_DSP_BUFFER_SIZE as u32;
+        state
+    }
+
+    /// Runs `input` through `effect.process` as one block with no side-chain.
+    pub fn process<E: Effect>(effect: &mut E, samplerate: u32, input: &[f32], output: &mut [f32], channels: usize) {
+        let frames = input.len() / channels;
+        let context = ProcessContext { samplerate, currdsptick: 0, sidechain: Interleaved::empty(frames) };
+        let input = unsafe { Interleaved::from_raw(input.as_ptr(), frames, channels) };
+        let output = unsafe { InterleavedMut::from_raw(output.as_mut_ptr(), frames, channels) };
+        effect.process(&context, input, output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes each input channel times its index plus one and remembers how it was called.
+    struct Recorder {
+        param: [f32; 2],
+        changes: Vec<(usize, f32)>,
+        calls: Vec<(u64, usize)>,
+    }
+
+    impl Effect for Recorder {
+        type Shared = ();
+
+        const DEINTERLEAVE: bool = true;
+
+        fn create(_state: &UnityAudioEffectState_Data) -> Self {
+            Recorder { param: [1.0, 2.0], changes: Vec::new(), calls: Vec::new() }
+        }
+
+        fn shared(&self) -> Arc<()> {
+            Arc::default()
+        }
+
+        fn parameters(&mut self) -> &mut [f32] {
+            &mut self.param
+        }
+
+        fn parameter_changed(&mut self, index: usize) {
+            self.changes.push((index, self.param[index]));
+        }
+
+        fn process_planar(&mut self, context: &ProcessContext, input: &Planar, output: &mut Planar) {
+            self.calls.push((context.currdsptick, output.iter_channels().next().map_or(0, |run| run.len())));
+            let mut inputs = input.iter_channels();
+            for (i, out) in output.iter_channels_mut().enumerate() {
+                // Output channels without an input stay as the adapter left them: silent.
+                if let Some(run) = inputs.next() {
+                    for (o, x) in out.iter_mut().zip(run) {
+                        *o = x * (i + 1) as f32;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn oversized_blocks_are_processed_in_chunks() {
+        let mut state = testing::state(48000);
+        state.dspbuffersize = 64;
+        let mut instance = Instance::<Recorder>::new(&state);
+        let audio = instance.audio.get_mut();
+        // 64 frames of 8 channels fit 256 frames of stereo at a time.
+        let frames = 1000;
+        let input: Vec<f32> = (0..frames * 2).map(|n| n as f32).collect();
+        let mut output = vec![f32::NAN; frames * 2];
+        let context = ProcessContext { samplerate: 48000, currdsptick: 5000, sidechain: Interleaved::empty(frames) };
+        let src = unsafe { Interleaved::from_raw(input.as_ptr(), frames, 2) };
+        let dst = unsafe { InterleavedMut::from_raw(output.as_mut_ptr(), frames, 2) };
+        audio.process(&context, src, dst);
+
+        assert_eq!(audio.effect.calls, [(5000, 256), (5256, 256), (5512, 256), (5768, 232)]);
+        for (n, frame) in output.chunks_exact(2).enumerate() {
+            assert_eq!(frame, [(2 * n) as f32, (2 * n + 1) as f32 * 2.0], "frame {n}");
+        }
+    }
+
+    #[test]
+    fn planar_output_starts_silent() {
+        let mut instance = Instance::<Recorder>::new(&testing::state(48000));
+        let input = [1.0f32; 16];
+        let mut output = [f32::NAN; 32];
+        let context = ProcessContext { samplerate: 48000, currdsptick: 0, sidechain: Interleaved::empty(16) };
+        let src = unsafe { Interleaved::from_raw(input.as_ptr(), 16, 1) };
+        let dst = unsafe { InterleavedMut::from_raw(output.as_mut_ptr(), 16, 2) };
+        instance.audio.get_mut().process(&context, src, dst);
+        assert!(output.chunks_exact(2).all(|frame| frame == [1.0, 0.0]));
+    }
+
+    #[test]
+    fn parameter_writes_reach_the_effect_before_the_next_block() {
+        let mut instance = Instance::<Recorder>::new(&testing::state(48000));
+        assert_eq!(instance.parameters.get(1), Some(2.0));
+        assert_eq!(instance.parameters.get(2), None);
+
+        assert!(instance.parameters.set(1, 5.0));
+        assert!(instance.parameters.set(0, 3.0));
+        assert!(instance.parameters.set(1, 6.0));
+        assert!(!instance.parameters.set(2, 0.0));
+        // Nothing reaches the effect until the audio thread picks the writes up.
+        let audio = instance.audio.get_mut();
+        assert_eq!(audio.effect.param, [1.0, 2.0]);
+
+        instance.parameters.apply(&mut audio.effect);
+        assert_eq!(audio.effect.param, [3.0, 6.0]);
+        assert_eq!(audio.effect.changes, [(0, 3.0), (1, 6.0)]);
+        // Writing the same value again still counts as a change.
+        instance.parameters.set(0, 3.0);
+        instance.parameters.apply(&mut audio.effect);
+        instance.parameters.apply(&mut audio.effect);
+        assert_eq!(audio.effect.changes, [(0, 3.0), (1, 6.0), (0, 3.0)]);
+    }
+}
@@ -1,18 +1,51 @@
+// Every exported callback receives raw pointers straight from Unity.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
 #[macro_use]
 mod unity_audio_dsp;
+mod audio_buffer;
+pub mod denormal;
+pub mod dsp;
+mod effect;
+mod realtime;
+pub mod transport;
+mod plugin_compressor;
+mod plugin_correlation_meter;
+mod plugin_equalizer;
+mod plugin_gate;
+mod plugin_limiter;
+mod plugin_loudness_meter;
+mod plugin_multiband;
+mod plugin_oscilloscope;
+mod plugin_pitch_detector;
 mod plugin_ring_modulator;
+mod plugin_spectrum_analyzer;
+mod plugin_stereo_widener;
+mod plugin_test_tone;
 
 use std::{
     cmp::min,
     ffi::CStr,
 };
 
+use effect::Effect;
+use plugin_compressor::Compressor;
+use plugin_correlation_meter::CorrelationMeter;
+use plugin_equalizer::Equalizer;
+use plugin_gate::Gate;
+use plugin_limiter::Limiter;
+use plugin_loudness_meter::LoudnessMeter;
+use plugin_multiband::Multiband;
+use plugin_oscilloscope::Oscilloscope;
+use plugin_pitch_detector::PitchDetector;
+use plugin_ring_modulator::RingModulator;
+use plugin_spectrum_analyzer::SpectrumAnalyzer;
+use plugin_stereo_widener::StereoWidener;
+use plugin_test_tone::TestTone;
+use transport::NoteDivision;
 use unity_audio_dsp::{
-    UnityAudioEffectDefinition, UnityAudioEffect_CreateCallback,
-    UnityAudioEffect_GetFloatBufferCallback, UnityAudioEffect_GetFloatParameterCallback,
-    UnityAudioEffect_ProcessCallback, UnityAudioEffect_ReleaseCallback,
-    UnityAudioEffect_SetFloatParameterCallback, UnityAudioParameterDefinition,
-    UNITY_AUDIO_PLUGIN_API_VERSION,
+    UnityAudioEffectDefinition, UnityAudioEffectDefinitionFlags_IsSideChainTarget,
+    UnityAudioParameterDefinition, UNITY_AUDIO_PLUGIN_API_VERSION,
 };
 
 macro_rules! cstr {
@@ -21,21 +54,219 @@ macro_rules! cstr {
     };
 }
 
+/// Description for tempo-synced parameters, listing the values `NoteDivision::from_param` accepts.
+macro_rules! note_divisions {
+    ($str:expr) => {
+        concat!($str, ": 0 = off, 1 = 1/1, 2 = 1/2, 3 = 1/2D, 4 = 1/2T, 5 = 1/4, 6 = 1/4D, 7 = 1/4T, 8 = 1/8, 9 = 1/8D, 10 = 1/8T, 11 = 1/16, 12 = 1/16D, 13 = 1/16T, 14 = 1/32")
+    };
+}
+
 // Export symbol
 unity_dsp_callback!(
     // This is the entry point of the plugin.
     export fn UnityGetAudioEffectDefinitions(desc_ptr: *mut *mut *mut UnityAudioEffectDefinition) -> i32 {
-        let ring_mod = declare_effect(
+        let ring_mod = declare_effect::<RingModulator>(
             "Rusty Ring Modulator",
-            plugin_ring_modulator::create_callback,
-            plugin_ring_modulator::release_callback,
-            plugin_ring_modulator::process_callback,
-            plugin_ring_modulator::set_float_parameter_callback,
-            plugin_ring_modulator::get_float_parameter_callback,
-            plugin_ring_modulator::get_float_buffer_callback,
+            EffectKind::Effect,
+            UnityAudioEffectDefinitionFlags_IsSideChainTarget,
             &[
                 declare_parameter("Frequency", "Hz", cstr!("The frequency of the sine wave"), 0.0, 22050.0, 1000.0, 1.0, 3.0),
                 declare_parameter("Mix Amount", "%", cstr!("The amount of mix!"), 0.0, 1.0, 0.5, 1.0, 1.0),
+                declare_parameter("Waveform", "", cstr!("Carrier waveform: 0 = sine, 1 = triangle, 2 = square, 3 = saw, 4 = noise"), 0.0, 4.0, 0.0, 1.0, 1.0),
+                declare_parameter("LFO Rate", "Hz", cstr!("How fast the LFO sweeps the carrier frequency"), 0.01, 20.0, 1.0, 1.0, 3.0),
+                declare_parameter("LFO Depth", "oct", cstr!("How far the LFO sweeps the carrier frequency, in octaves either way"), 0.0, 4.0, 0.0, 1.0, 1.0),
+                declare_parameter("Side-chain Mode", "", cstr!("0 = modulate with the internal carrier, 1 = modulate with the side-chain input"), 0.0, 1.0, 0.0, 1.0, 1.0),
+                declare_parameter("Stereo Spread", "%", cstr!("Carrier phase offset between output channels, up to half a cycle; noise has no phase and ignores it"), 0.0, 1.0, 0.0, 100.0, 1.0),
+                declare_parameter("Tempo", "BPM", cstr!("Tempo for synced LFO rates; 0 follows the tempo set from script"), 0.0, 300.0, 0.0, 1.0, 1.0),
+                declare_parameter("LFO Sync", "", cstr!(note_divisions!("Locks the LFO rate to the tempo")), 0.0, NoteDivision::MAX_PARAM, 0.0, 1.0, 1.0),
+            ],
+        );
+
+        let test_tone = declare_effect::<TestTone>(
+            "Rusty Test Tone",
+            EffectKind::Generator { channels: 2 },
+            0,
+            &[
+                declare_parameter("Frequency", "Hz", cstr!("The frequency of the test tone"), 20.0, 20000.0, 440.0, 1.0, 3.0),
+                declare_parameter("Level", "dB", cstr!("The output level of the test tone"), -96.0, 0.0, -12.0, 1.0, 1.0),
+            ],
+        );
+
+        let equalizer = declare_effect::<Equalizer>(
+            "Rusty Equalizer",
+            EffectKind::Effect,
+            0,
+            &[
+                declare_parameter("Master Gain", "dB", cstr!("Overall output gain"), -24.0, 24.0, 0.0, 1.0, 1.0),
+                declare_parameter("LowShelf Freq", "Hz", cstr!("Low shelf corner frequency"), 20.0, 20000.0, 100.0, 1.0, 3.0),
+                declare_parameter("LowShelf Gain", "dB", cstr!("Low shelf gain"), -24.0, 24.0, 0.0, 1.0, 1.0),
+                declare_parameter("LowShelf Q", "", cstr!("Low shelf slope"), 0.1, 10.0, 0.707, 1.0, 3.0),
+                declare_parameter("Peak1 Freq", "Hz", cstr!("First peaking band center frequency"), 20.0, 20000.0, 300.0, 1.0, 3.0),
+                declare_parameter("Peak1 Gain", "dB", cstr!("First peaking band gain"), -24.0, 24.0, 0.0, 1.0, 1.0),
+                declare_parameter("Peak1 Q", "", cstr!("First peaking band Q"), 0.1, 10.0, 1.0, 1.0, 3.0),
+                declare_parameter("Peak2 Freq", "Hz", cstr!("Second peaking band center frequency"), 20.0, 20000.0, 1000.0, 1.0, 3.0),
+                declare_parameter("Peak2 Gain", "dB", cstr!("Second peaking band gain"), -24.0, 24.0, 0.0, 1.0, 1.0),
+                declare_parameter("Peak2 Q", "", cstr!("Second peaking band Q"), 0.1, 10.0, 1.0, 1.0, 3.0),
+                declare_parameter("Peak3 Freq", "Hz", cstr!("Third peaking band center frequency"), 20.0, 20000.0, 3000.0, 1.0, 3.0),
+                declare_parameter("Peak3 Gain", "dB", cstr!("Third peaking band gain"), -24.0, 24.0, 0.0, 1.0, 1.0),
+                declare_parameter("Peak3 Q", "", cstr!("Third peaking band Q"), 0.1, 10.0, 1.0, 1.0, 3.0),
+                declare_parameter("HighShelf Freq", "Hz", cstr!("High shelf corner frequency"), 20.0, 20000.0, 8000.0, 1.0, 3.0),
+                declare_parameter("HighShelf Gain", "dB", cstr!("High shelf gain"), -24.0, 24.0, 0.0, 1.0, 1.0),
+                declare_parameter("HighShelf Q", "", cstr!("High shelf slope"), 0.1, 10.0, 0.707, 1.0, 3.0),
+                declare_parameter("HP Freq", "Hz", cstr!("High-pass cutoff; 0 turns the high-pass off"), 0.0, 2000.0, 0.0, 1.0, 3.0),
+                declare_parameter("HP Q", "", cstr!("High-pass resonance"), 0.1, 10.0, 0.707, 1.0, 3.0),
+                declare_parameter("LP Freq", "Hz", cstr!("Low-pass cutoff; the maximum turns the low-pass off"), 100.0, 22000.0, 22000.0, 1.0, 3.0),
+                declare_parameter("LP Q", "", cstr!("Low-pass resonance"), 0.1, 10.0, 0.707, 1.0, 3.0),
+            ],
+        );
+
+        let multiband = declare_effect::<Multiband>(
+            "Rusty Multiband",
+            EffectKind::Effect,
+            0,
+            &[
+                declare_parameter("Master Gain", "dB", cstr!("Overall output gain"), -24.0, 24.0, 0.0, 1.0, 1.0),
+                declare_parameter("Bands", "", cstr!("Number of bands, 3 or 4; the third crossover is only used with 4"), 3.0, 4.0, 3.0, 1.0, 1.0),
+                declare_parameter("Crossover 1", "Hz", cstr!("Split between the first and second band"), 20.0, 20000.0, 200.0, 1.0, 3.0),
+                declare_parameter("Crossover 2", "Hz", cstr!("Split between the second and third band"), 20.0, 20000.0, 2000.0, 1.0, 3.0),
+                declare_parameter("Crossover 3", "Hz", cstr!("Split between the third and fourth band"), 20.0, 20000.0, 8000.0, 1.0, 3.0),
+                declare_parameter("B1 Threshold", "dB", cstr!("First band level above which compression starts"), -60.0, 0.0, -24.0, 1.0, 1.0),
+                declare_parameter("B1 Ratio", ":1", cstr!("First band compression ratio"), 1.0, 20.0, 2.0, 1.0, 2.0),
+                declare_parameter("B1 Attack", "ms", cstr!("First band attack time"), 0.1, 200.0, 20.0, 1.0, 3.0),
+                declare_parameter("B1 Release", "ms", cstr!("First band release time"), 10.0, 2000.0, 200.0, 1.0, 3.0),
+                declare_parameter("B1 Makeup", "dB", cstr!("First band gain after compression"), -24.0, 24.0, 0.0, 1.0, 1.0),
+                declare_parameter("B2 Threshold", "dB", cstr!("Second band level above which compression starts"), -60.0, 0.0, -24.0, 1.0, 1.0),
+                declare_parameter("B2 Ratio", ":1", cstr!("Second band compression ratio"), 1.0, 20.0, 2.0, 1.0, 2.0),
+                declare_parameter("B2 Attack", "ms", cstr!("Second band attack time"), 0.1, 200.0, 10.0, 1.0, 3.0),
+                declare_parameter("B2 Release", "ms", cstr!("Second band release time"), 10.0, 2000.0, 150.0, 1.0, 3.0),
+                declare_parameter("B2 Makeup", "dB", cstr!("Second band gain after compression"), -24.0, 24.0, 0.0, 1.0, 1.0),
+                declare_parameter("B3 Threshold", "dB", cstr!("Third band level above which compression starts"), -60.0, 0.0, -24.0, 1.0, 1.0),
+                declare_parameter("B3 Ratio", ":1", cstr!("Third band compression ratio"), 1.0, 20.0, 2.0, 1.0, 2.0),
+                declare_parameter("B3 Attack", "ms", cstr!("Third band attack time"), 0.1, 200.0, 5.0, 1.0, 3.0),
+                declare_parameter("B3 Release", "ms", cstr!("Third band release time"), 10.0, 2000.0, 100.0, 1.0, 3.0),
+                declare_parameter("B3 Makeup", "dB", cstr!("Third band gain after compression"), -24.0, 24.0, 0.0, 1.0, 1.0),
+                declare_parameter("B4 Threshold", "dB", cstr!("Fourth band level above which compression starts"), -60.0, 0.0, -24.0, 1.0, 1.0),
+                declare_parameter("B4 Ratio", ":1", cstr!("Fourth band compression ratio"), 1.0, 20.0, 2.0, 1.0, 2.0),
+                declare_parameter("B4 Attack", "ms", cstr!("Fourth band attack time"), 0.1, 200.0, 2.0, 1.0, 3.0),
+                declare_parameter("B4 Release", "ms", cstr!("Fourth band release time"), 10.0, 2000.0, 80.0, 1.0, 3.0),
+                declare_parameter("B4 Makeup", "dB", cstr!("Fourth band gain after compression"), -24.0, 24.0, 0.0, 1.0, 1.0),
+            ],
+        );
+
+        let compressor = declare_effect::<Compressor>(
+            "Rusty Compressor",
+            EffectKind::Effect,
+            UnityAudioEffectDefinitionFlags_IsSideChainTarget,
+            &[
+                declare_parameter("Threshold", "dB", cstr!("Level above which compression starts"), -60.0, 0.0, -18.0, 1.0, 1.0),
+                declare_parameter("Ratio", ":1", cstr!("Compression ratio; the maximum behaves as a limiter"), 1.0, 50.0, 4.0, 1.0, 2.0),
+                declare_parameter("Knee", "dB", cstr!("Width of the soft knee around the threshold; 0 is a hard knee"), 0.0, 24.0, 6.0, 1.0, 1.0),
+                declare_parameter("Attack", "ms", cstr!("How fast gain reduction sets in"), 0.1, 200.0, 10.0, 1.0, 3.0),
+                declare_parameter("Release", "ms", cstr!("How fast gain reduction recovers"), 10.0, 2000.0, 100.0, 1.0, 3.0),
+                declare_parameter("Makeup", "dB", cstr!("Gain applied after compression"), -24.0, 24.0, 0.0, 1.0, 1.0),
+                declare_parameter("Detection", "", cstr!("0 = peak, 1 = RMS"), 0.0, 1.0, 1.0, 1.0, 1.0),
+                declare_parameter("Lookahead", "ms", cstr!("Delays the audio so gain reduction can start before transients"), 0.0, 20.0, 0.0, 1.0, 1.0),
+                declare_parameter("Side-chain Mode", "", cstr!("0 = detect on the input, 1 = key off the side-chain input"), 0.0, 1.0, 0.0, 1.0, 1.0),
+            ],
+        );
+
+        let limiter = declare_effect::<Limiter>(
+            "Rusty Limiter",
+            EffectKind::Effect,
+            0,
+            &[
+                declare_parameter("Input Gain", "dB", cstr!("Drive into the limiter"), 0.0, 24.0, 0.0, 1.0, 1.0),
+                declare_parameter("Ceiling", "dBTP", cstr!("Highest true peak the output may reach"), -20.0, 0.0, -1.0, 1.0, 1.0),
+                declare_parameter("Release", "ms", cstr!("How fast gain reduction recovers"), 1.0, 1000.0, 100.0, 1.0, 3.0),
+                declare_parameter("Lookahead", "ms", cstr!("Delay that lets the gain ramp down before a peak arrives"), 0.5, 10.0, 2.0, 1.0, 1.0),
+            ],
+        );
+
+        let gate = declare_effect::<Gate>(
+            "Rusty Gate",
+            EffectKind::Effect,
+            UnityAudioEffectDefinitionFlags_IsSideChainTarget,
+            &[
+                declare_parameter("Threshold", "dB", cstr!("Level at which the gate opens"), -80.0, 0.0, -40.0, 1.0, 1.0),
+                declare_parameter("Hysteresis", "dB", cstr!("How far below the threshold the level must fall before the gate closes"), 0.0, 24.0, 6.0, 1.0, 1.0),
+                declare_parameter("Attack", "ms", cstr!("How fast the gate opens"), 0.01, 100.0, 1.0, 1.0, 3.0),
+                declare_parameter("Hold", "ms", cstr!("How long the gate stays open after the level drops"), 0.0, 2000.0, 50.0, 1.0, 3.0),
+                declare_parameter("Release", "ms", cstr!("How fast the gate closes"), 1.0, 4000.0, 100.0, 1.0, 3.0),
+                declare_parameter("Range", "dB", cstr!("Attenuation while closed"), -80.0, 0.0, -80.0, 1.0, 1.0),
+                declare_parameter("Ratio", ":1", cstr!("Downward expansion below the threshold; the maximum gates straight to the range"), 1.0, 50.0, 50.0, 1.0, 2.0),
+                declare_parameter("Side-chain Mode", "", cstr!("0 = detect on the input, 1 = key off the side-chain input"), 0.0, 1.0, 0.0, 1.0, 1.0),
+            ],
+        );
+
+        let loudness_meter = declare_effect::<LoudnessMeter>(
+            "Rusty Loudness Meter",
+            EffectKind::Effect,
+            0,
+            &[
+                declare_parameter("Reset", "", cstr!("Going from 0 to 1 restarts the integrated loudness, loudness range and true peak"), 0.0, 1.0, 0.0, 1.0, 1.0),
+                declare_parameter("Index", "", cstr!("Slot script reads the readings from with RustyLoudnessMeterGetIntegrated and friends"), 0.0, (plugin_loudness_meter::SLOTS - 1) as f32, 0.0, 1.0, 1.0),
+            ],
+        );
+
+        let correlation_meter = declare_effect::<CorrelationMeter>(
+            "Rusty Correlation Meter",
+            EffectKind::Effect,
+            0,
+            &[
+                declare_parameter("Window", "ms", cstr!("Averaging time of the correlation and balance readings"), 10.0, 3000.0, 300.0, 1.0, 3.0),
+                declare_parameter("Decimation", "", cstr!("Keeps every Nth frame as a vectorscope point"), 1.0, 64.0, 4.0, 1.0, 1.0),
+            ],
+        );
+
+        let oscilloscope = declare_effect::<Oscilloscope>(
+            "Rusty Oscilloscope",
+            EffectKind::Effect,
+            0,
+            &[
+                declare_parameter("Window", "ms", cstr!("Length of each captured trace"), 1.0, 500.0, 20.0, 1.0, 3.0),
+                declare_parameter("Trigger Level", "", cstr!("A capture starts when the signal rises through this level"), -1.0, 1.0, 0.0, 1.0, 1.0),
+                declare_parameter("Holdoff", "ms", cstr!("Time after a capture during which triggers are ignored"), 0.0, 1000.0, 10.0, 1.0, 3.0),
+                declare_parameter("Channel", "", cstr!("Channel to trigger on and capture, counting from 0"), 0.0, 7.0, 0.0, 1.0, 1.0),
+            ],
+        );
+
+        let spectrum_analyzer = declare_effect::<SpectrumAnalyzer>(
+            "Rusty Spectrum Analyzer",
+            EffectKind::Effect,
+            0,
+            &[
+                declare_parameter("FFT Size", "", cstr!("Analysis length as a power of two: 8 = 256 ... 14 = 16384 points"), 8.0, 14.0, 11.0, 1.0, 1.0),
+                declare_parameter("Window", "", cstr!("0 = Hann, 1 = Blackman-Harris, 2 = flat-top"), 0.0, 2.0, 0.0, 1.0, 1.0),
+                declare_parameter("Averaging", "ms", cstr!("Time constant of the averaged spectrum; 0 shows every frame as is"), 0.0, 5000.0, 100.0, 1.0, 3.0),
+                declare_parameter("Peak Hold", "ms", cstr!("How long peaks stay put before falling"), 0.0, 10000.0, 1000.0, 1.0, 3.0),
+                declare_parameter("Peak Fall", "dB/s", cstr!("How fast peaks fall once the hold time is over"), 0.0, 120.0, 12.0, 1.0, 1.0),
+                declare_parameter("Update Rate", "Hz", cstr!("Analysis frames per second, independent of the mixer block size"), 1.0, 200.0, 30.0, 1.0, 3.0),
+            ],
+        );
+
+        let pitch_detector = declare_effect::<PitchDetector>(
+            "Rusty Pitch Detector",
+            EffectKind::Effect,
+            0,
+            &[
+                declare_parameter("Index", "", cstr!("Slot script reads the result from with RustyPitchDetectorGetFreq"), 0.0, (plugin_pitch_detector::SLOTS - 1) as f32, 0.0, 1.0, 1.0),
+                declare_parameter("Min Freq", "Hz", cstr!("Lowest pitch to look for"), 40.0, 1000.0, 70.0, 1.0, 3.0),
+                declare_parameter("Max Freq", "Hz", cstr!("Highest pitch to look for"), 100.0, 4000.0, 1500.0, 1.0, 3.0),
+                declare_parameter("Threshold", "", cstr!("How periodic the signal must be to count as pitched; lower is stricter"), 0.01, 0.5, 0.15, 1.0, 1.0),
+                declare_parameter("Gate", "dB", cstr!("Input below this RMS level reports no pitch"), -100.0, 0.0, -60.0, 1.0, 1.0),
+            ],
+        );
+
+        let stereo_widener = declare_effect::<StereoWidener>(
+            "Rusty Stereo Widener",
+            EffectKind::Effect,
+            0,
+            &[
+                declare_parameter("Width", "%", cstr!("Side level relative to the mid: 0 = mono, 1 = unchanged, 2 = twice as wide"), 0.0, 2.0, 1.0, 100.0, 1.0),
+                declare_parameter("Mid Gain", "dB", cstr!("Gain of the mid (L+R) signal"), -24.0, 12.0, 0.0, 1.0, 1.0),
+                declare_parameter("Side Gain", "dB", cstr!("Gain of the side (L-R) signal"), -24.0, 12.0, 0.0, 1.0, 1.0),
+                declare_parameter("Bass Mono", "Hz", cstr!("Frequencies below this are made mono; 0 turns it off"), 0.0, 500.0, 0.0, 1.0, 3.0),
+                declare_parameter("Haas Delay", "ms", cstr!("Delays the right channel, or the left when negative, to widen by precedence"), -30.0, 30.0, 0.0, 1.0, 1.0),
             ],
         );
 
@@ -43,6 +274,18 @@ unity_dsp_callback!(
         // TODO: Leaking pointers...
         let array_of_effects = [
             Box::leak(Box::new(ring_mod)) as *mut UnityAudioEffectDefinition,
+            Box::leak(Box::new(test_tone)) as *mut UnityAudioEffectDefinition,
+            Box::leak(Box::new(equalizer)) as *mut UnityAudioEffectDefinition,
+            Box::leak(Box::new(multiband)) as *mut UnityAudioEffectDefinition,
+            Box::leak(Box::new(compressor)) as *mut UnityAudioEffectDefinition,
+            Box::leak(Box::new(limiter)) as *mut UnityAudioEffectDefinition,
+            Box::leak(Box::new(gate)) as *mut UnityAudioEffectDefinition,
+            Box::leak(Box::new(loudness_meter)) as *mut UnityAudioEffectDefinition,
+            Box::leak(Box::new(correlation_meter)) as *mut UnityAudioEffectDefinition,
+            Box::leak(Box::new(oscilloscope)) as *mut UnityAudioEffectDefinition,
+            Box::leak(Box::new(spectrum_analyzer)) as *mut UnityAudioEffectDefinition,
+            Box::leak(Box::new(pitch_detector)) as *mut UnityAudioEffectDefinition,
+            Box::leak(Box::new(stereo_widener)) as *mut UnityAudioEffectDefinition,
         ];
 
         let array_ptr = Box::leak(Box::new(array_of_effects)) as *mut *mut UnityAudioEffectDefinition;
@@ -56,14 +299,91 @@ unity_dsp_callback!(
     }
 );
 
-fn declare_effect(
+unity_dsp_callback!(
+    // Called from C# by the music system so tempo-synced effects follow it:
+    // [DllImport("libaudiotest")] static extern void RustySetTempo(float bpm, ulong downbeatDspTick);
+    export fn RustySetTempo(bpm: f32, downbeat_dsptick: u64) {
+        transport::set_global_tempo(bpm, downbeat_dsptick);
+    }
+);
+
+unity_dsp_callback!(
+    // Polled from C# with the "Index" parameter of a Rusty Pitch Detector instance:
+    // [DllImport("libaudiotest")] static extern float RustyPitchDetectorGetFreq(int index);
+    // Returns 0 while the input is silent or unpitched.
+    export fn RustyPitchDetectorGetFreq(index: i32) -> f32 {
+        plugin_pitch_detector::published(index).0
+    }
+);
+
+unity_dsp_callback!(
+    // [DllImport("libaudiotest")] static extern float RustyPitchDetectorGetConfidence(int index);
+    export fn RustyPitchDetectorGetConfidence(index: i32) -> f32 {
+        plugin_pitch_detector::published(index).1
+    }
+);
+
+unity_dsp_callback!(
+    // Polled from C# with the "Index" parameter of a Rusty Loudness Meter instance, in LUFS:
+    // [DllImport("libaudiotest")] static extern float RustyLoudnessMeterGetMomentary(int index);
+    // Loudness reads -200 LUFS until there's something to measure.
+    export fn RustyLoudnessMeterGetMomentary(index: i32) -> f32 {
+        plugin_loudness_meter::published(index).momentary.load()
+    }
+);
+
+unity_dsp_callback!(
+    // [DllImport("libaudiotest")] static extern float RustyLoudnessMeterGetShortTerm(int index);
+    export fn RustyLoudnessMeterGetShortTerm(index: i32) -> f32 {
+        plugin_loudness_meter::published(index).short_term.load()
+    }
+);
+
+unity_dsp_callback!(
+    // [DllImport("libaudiotest")] static extern float RustyLoudnessMeterGetIntegrated(int index);
+    export fn RustyLoudnessMeterGetIntegrated(index: i32) -> f32 {
+        plugin_loudness_meter::published(index).integrated.load()
+    }
+);
+
+unity_dsp_callback!(
+    // In LU:
+    // [DllImport("libaudiotest")] static extern float RustyLoudnessMeterGetRange(int index);
+    export fn RustyLoudnessMeterGetRange(index: i32) -> f32 {
+        plugin_loudness_meter::published(index).range.load()
+    }
+);
+
+unity_dsp_callback!(
+    // In dBTP:
+    // [DllImport("libaudiotest")] static extern float RustyLoudnessMeterGetTruePeak(int index);
+    export fn RustyLoudnessMeterGetTruePeak(index: i32) -> f32 {
+        plugin_loudness_meter::published(index).true_peak.load()
+    }
+);
+
+/// Decides what Unity reports in `UnityAudioEffectDefinition::channels`.
+#[derive(Clone, Copy)]
+enum EffectKind {
+    /// Processes whatever channel layout the mixer group hands it.
+    Effect,
+    /// Produces its own signal with a fixed channel count and never reads `in_buffer`.
+    Generator { channels: u32 },
+}
+
+impl EffectKind {
+    fn channels(self) -> u32 {
+        match self {
+            EffectKind::Effect => 0,
+            EffectKind::Generator { channels } => channels,
+        }
+    }
+}
+
+fn declare_effect<E: Effect>(
     name: &str,
-    create_callback: UnityAudioEffect_CreateCallback,
-    release_callback: UnityAudioEffect_ReleaseCallback,
-    process_callback: UnityAudioEffect_ProcessCallback,
-    set_float_callback: UnityAudioEffect_SetFloatParameterCallback,
-    get_float_callback: UnityAudioEffect_GetFloatParameterCallback,
-    get_float_buffer_callback: UnityAudioEffect_GetFloatBufferCallback,
+    kind: EffectKind,
+    flags: u64,
     param_defs: &[UnityAudioParameterDefinition],
     //declareParametersFn: impl FnOnce(&mut Vec<UnityAudioParameterDefinition>),
 ) -> UnityAudioEffectDefinition {
@@ -75,21 +395,22 @@ fn declare_effect(
         apiversion: UNITY_AUDIO_PLUGIN_API_VERSION,
         pluginversion: 0x010000,
         name: fit_cstr_array(name.as_bytes()),
-        create: Some(create_callback),
-        release: Some(release_callback),
-        process: Some(process_callback),
-        setfloatparameter: Some(set_float_callback),
-        getfloatparameter: Some(get_float_callback),
-        getfloatbuffer: Some(get_float_buffer_callback),
+        create: Some(effect::create_callback::<E>),
+        release: Some(effect::release_callback::<E>),
+        process: Some(effect::process_callback::<E>),
+        setfloatparameter: Some(effect::set_float_parameter_callback::<E>),
+        getfloatparameter: Some(effect::get_float_parameter_callback::<E>),
+        getfloatbuffer: Some(effect::get_float_buffer_callback::<E>),
         numparameters: param_defs.len() as u32,
-        channels: 2,
-        flags: 0,
+        channels: kind.channels(),
+        flags,
         reset: None,
         setposition: None,
         paramdefs: Box::leak(params_ptr).as_ptr(), // TODO: Leaking memory.
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn declare_parameter(
     name: &str,
     unit: &str,
@@ -119,3 +440,102 @@ fn fit_cstr_array<const SIZE: usize>(slice: &[u8]) -> [u8; SIZE] {
     array[min(limit, SIZE - 1)] = 0; // insert null-terminator
     array
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::realtime::AudioThreadGuard;
+    use crate::unity_audio_dsp::{UnityAudioEffectState, UnityAudioEffectStateFlags_IsSideChainTarget};
+
+    fn definitions() -> &'static [*mut UnityAudioEffectDefinition] {
+        let mut array = std::ptr::null_mut();
+        let count = UnityGetAudioEffectDefinitions(&mut array);
+        unsafe { std::slice::from_raw_parts(array, count as usize) }
+    }
+
+    /// Every registered effect, at every parameter's extremes, fed a side-chain and over the channel
+    /// layouts Unity uses, must process without allocating or blocking.
+    #[test]
+    fn process_is_real_time_safe() {
+        const FRAMES: usize = 1024;
+        let sidechain: Vec<f32> = (0..FRAMES * 8).map(|n| (n as f32 * 0.01).sin()).collect();
+        for &definition in definitions() {
+            let definition = unsafe { &*definition };
+            let mut data = effect::testing::state(48000);
+            data.sidechainbuffer = sidechain.as_ptr();
+            data.flags = UnityAudioEffectStateFlags_IsSideChainTarget;
+            let mut state = UnityAudioEffectState { data };
+            definition.create.unwrap()(&mut state);
+            let params = unsafe { std::slice::from_raw_parts(definition.paramdefs, definition.numparameters as usize) };
+
+            let settings: [fn(&UnityAudioParameterDefinition) -> f32; 3] = [|p| p.min, |p| p.max, |p| p.defaultval];
+            for setting in settings {
+                for (index, param) in params.iter().enumerate() {
+                    definition.setfloatparameter.unwrap()(&mut state, index as i32, setting(param));
+                }
+                for (in_ch, out_ch) in [(1, 1), (2, 2), (1, 2), (2, 1), (6, 6), (8, 8), (6, 2)] {
+                    let input: Vec<f32> = (0..FRAMES * in_ch).map(|n| (n as f32 * 0.003).sin()).collect();
+                    let mut output = vec![0.0f32; FRAMES * out_ch];
+                    let _audio_thread = AudioThreadGuard::enter();
+                    for _ in 0..4 {
+                        let process = definition.process.unwrap();
+                        process(&mut state, input.as_ptr(), output.as_mut_ptr(), FRAMES as u32, in_ch as i32, out_ch as i32);
+                        unsafe { state.data.currdsptick += FRAMES as u64 };
+                    }
+                }
+            }
+
+            definition.release.unwrap()(&mut state);
+        }
+    }
+
+    /// Unity polls parameters and analysis buffers from the GUI thread while the audio thread is
+    /// inside `process`; every effect has to take that without tearing or panicking.
+    #[test]
+    fn gui_calls_run_alongside_process() {
+        const FRAMES: usize = 512;
+        const NAMES: [&[u8]; 15] = [
+            b"GainReduction\0", b"Response\0", b"Spectrum\0", b"Peaks\0", b"Correlation\0",
+            b"Balance\0", b"Vectorscope\0", b"Scope\0", b"Frequency\0", b"Confidence\0",
+            b"Momentary\0", b"ShortTerm\0", b"Integrated\0", b"Range\0", b"TruePeak\0",
+        ];
+        let input: Vec<f32> = (0..FRAMES * 2).map(|n| (n as f32 * 0.003).sin()).collect();
+        for &definition in definitions() {
+            let definition = unsafe { &*definition };
+            let mut state = UnityAudioEffectState { data: effect::testing::state(48000) };
+            definition.create.unwrap()(&mut state);
+            let params = unsafe { std::slice::from_raw_parts(definition.paramdefs, definition.numparameters as usize) };
+            let values: Vec<(f32, f32)> = params.iter().map(|p| (p.min, p.defaultval)).collect();
+            // Raw pointers aren't `Send`; the callbacks only read `state` itself.
+            let address = &mut state as *mut UnityAudioEffectState as usize;
+            let set = definition.setfloatparameter.unwrap();
+            let get = definition.getfloatparameter.unwrap();
+            let get_buffer = definition.getfloatbuffer.unwrap();
+
+            std::thread::scope(|scope| {
+                scope.spawn(|| {
+                    let state = address as *mut UnityAudioEffectState;
+                    let mut buffer = [0.0f32; 256];
+                    for round in 0..200 {
+                        for (index, &(min, default)) in values.iter().enumerate() {
+                            set(state, index as i32, if round % 2 == 0 { min } else { default });
+                            let mut read = 0.0;
+                            get(state, index as i32, &mut read, std::ptr::null_mut());
+                        }
+                        for name in NAMES {
+                            get_buffer(state, name.as_ptr(), buffer.as_mut_ptr(), buffer.len() as i32);
+                        }
+                    }
+                });
+
+                let mut output = vec![0.0f32; FRAMES * 2];
+                let process = definition.process.unwrap();
+                for _ in 0..200 {
+                    process(address as *mut _, input.as_ptr(), output.as_mut_ptr(), FRAMES as u32, 2, 2);
+                }
+            });
+
+            definition.release.unwrap()(&mut state);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use crate::audio_buffer::{remix, Interleaved, InterleavedMut};
+use crate::dsp::dynamics::{
+    compress_db, db_to_gain, Ballistics, Detection, GainReductionMeter, LevelDetector, SidechainKey,
+};
+use crate::dsp::history::HistoryBuffer;
+use crate::effect::{Effect, Parameters, ProcessContext, MAX_CHANNELS};
+use crate::unity_audio_dsp::UnityAudioEffectState_Data;
+
+const PARAM_COUNT: usize = 9;
+const PARAM_THRESHOLD: usize = 0;
+const PARAM_RATIO: usize = 1;
+const PARAM_KNEE: usize = 2;
+const PARAM_ATTACK: usize = 3;
+const PARAM_RELEASE: usize = 4;
+const PARAM_MAKEUP: usize = 5;
+const PARAM_DETECTION: usize = 6;
+const PARAM_LOOKAHEAD: usize = 7;
+const PARAM_SIDECHAIN: usize = 8;
+
+/// Matches the "Lookahead" parameter's maximum.
+const MAX_LOOKAHEAD_MS: f32 = 20.0;
+const RMS_WINDOW_MS: f32 = 10.0;
+
+pub struct Compressor {
+    param: [f32; PARAM_COUNT],
+    samplerate: f32,
+    dirty: bool,
+    detector: LevelDetector,
+    envelope: Ballistics,
+    /// Delays the audio behind the detector by the lookahead time, one line per channel.
+    delay: Vec<HistoryBuffer>,
+    meter: Arc<GainReductionMeter>,
+}
+
+impl Compressor {
+    fn update(&mut self) {
+        let p = &self.param;
+        let mode = if p[PARAM_DETECTION] >= 0.5 { Detection::Rms } else { Detection::Peak };
+        self.detector.set_mode(mode);
+        self.detector.set_rms_window(RMS_WINDOW_MS, self.samplerate);
+        self.envelope.set_times(p[PARAM_ATTACK], p[PARAM_RELEASE], self.samplerate);
+    }
+}
+
+impl Effect for Compressor {
+    type Shared = GainReductionMeter;
+
+    fn create(state: &UnityAudioEffectState_Data) -> Self {
+        let delay_len = (MAX_LOOKAHEAD_MS * 0.001 * state.samplerate as f32) as usize + 4;
+        Compressor {
+            param: [-18.0, 4.0, 6.0, 10.0, 100.0, 0.0, 1.0, 0.0, 0.0],
+            samplerate: state.samplerate as f32,
+            dirty: true,
+            detector: LevelDetector::new(Detection::Rms),
+            envelope: Ballistics::default(),
+            delay: (0..MAX_CHANNELS).map(|_| HistoryBuffer::new(delay_len)).collect(),
+            meter: Arc::default(),
+        }
+    }
+
+    fn shared(&self) -> Arc<GainReductionMeter> {
+        self.meter.clone()
+    }
+
+    fn parameters(&mut self) -> &mut [f32] {
+        &mut self.param
+    }
+
+    fn parameter_changed(&mut self, _index: usize) {
+        self.dirty = true;
+    }
+
+    fn process(&mut self, context: &ProcessContext, input: Interleaved, mut output: InterleavedMut) {
+        if self.dirty || self.samplerate != context.samplerate as f32 {
+            self.samplerate = context.samplerate as f32;
+            self.update();
+            self.dirty = false;
+        }
+
+        remix(&input, &mut output);
+
+        let p = &self.param;
+        let (threshold, ratio, knee) = (p[PARAM_THRESHOLD], p[PARAM_RATIO], p[PARAM_KNEE]);
+        let makeup = db_to_gain(p[PARAM_MAKEUP]);
+        let lookahead = (p[PARAM_LOOKAHEAD] * 0.001 * self.samplerate) as usize;
+        let lookahead = lookahead.min(self.delay[0].len() - 1);
+
+        let key = SidechainKey::new(&context.sidechain, p[PARAM_SIDECHAIN] >= 0.5);
+
+        let mut deepest = 0.0f32;
+        for (n, frame) in output.iter_frames_mut().enumerate() {
+            let level = self.detector.process(key.peak(n, frame));
+            let reduction = compress_db(level, threshold, ratio, knee) - level;
+            let smoothed = self.envelope.process(reduction);
+            deepest = deepest.min(smoothed);
+            let gain = db_to_gain(smoothed) * makeup;
+
+            for (sample, delay) in frame.iter_mut().zip(&mut self.delay) {
+                delay.feed(*sample);
+                *sample = delay.read(lookahead) * gain;
+            }
+        }
+        self.meter.update(deepest, self.envelope.value());
+    }
+
+    fn get_float_buffer(meter: &GainReductionMeter, _parameters: &Parameters, name: &CStr, buffer: &mut [f32]) {
+        if name.to_bytes() == b"GainReduction" {
+            if let Some(out) = buffer.first_mut() {
+                *out = meter.take();
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use crate::audio_buffer::{remix, Interleaved, InterleavedMut};
+use crate::dsp::atomic::AtomicF32;
+use crate::dsp::dynamics::time_coefficient;
+use crate::dsp::ring_buffer::RingBuffer;
+use crate::effect::{Effect, Parameters, ProcessContext};
+use crate::unity_audio_dsp::UnityAudioEffectState_Data;
+
+const PARAM_COUNT: usize = 2;
+const PARAM_WINDOW: usize = 0;
+const PARAM_DECIMATION: usize = 1;
+
+/// Vectorscope points queued for the GUI; roughly a second at the default decimation.
+const SCOPE_POINTS: usize = 16384;
+
+/// Running averages of the products the meters are built from.
+#[derive(Clone, Copy, Debug, Default)]
+struct Averages {
+    left_right: f32,
+    left: f32,
+    right: f32,
+    mid: f32,
+    side: f32,
+}
+
+/// Pass-through stereo analysis: phase correlation, mid/side balance and a vectorscope.
+///
+/// Mono input reads as fully correlated; only the first two channels of wider layouts are looked at.
+pub struct CorrelationMeter {
+    param: [f32; PARAM_COUNT],
+    averages: Averages,
+    countdown: usize,
+    shared: Arc<CorrelationShared>,
+}
+
+/// Meter readings as of the end of the last block, and the vectorscope points queued since.
+pub struct CorrelationShared {
+    correlation: AtomicF32,
+    balance: AtomicF32,
+    points: RingBuffer<(f32, f32)>,
+}
+
+impl Averages {
+    /// +1 for identical channels, 0 for unrelated ones, -1 for one channel inverted against the
+    /// other, which cancels when summed to mono. Silence reads 0.
+    fn correlation(&self) -> f32 {
+        let norm = (self.left * self.right).sqrt();
+        if norm > 1e-10 { (self.left_right / norm).clamp(-1.0, 1.0) } else { 0.0 }
+    }
+
+    /// +1 when everything is in the middle, -1 when everything is at the sides, 0 for balanced
+    /// wide material.
+    fn balance(&self) -> f32 {
+        let total = self.mid + self.side;
+        if total > 1e-10 { (self.mid - self.side) / total } else { 0.0 }
+    }
+}
+
+impl CorrelationShared {
+    /// Newest `(buffer.len() - 1) / 2` points as left/right pairs, oldest first. Like
+    /// `HistoryBuffer::read_buffer`, the final slot receives the number of points written.
+    fn read_vectorscope(&self, buffer: &mut [f32]) {
+        let Some((count, target)) = buffer.split_last_mut() else {
+            return;
+        };
+        let wanted = target.len() / 2;
+        self.points.skip(self.points.len().saturating_sub(wanted));
+        let mut written = 0;
+        for pair in target.chunks_exact_mut(2) {
+            let Some((left, right)) = self.points.pop() else {
+                break;
+            };
+            pair[0] = left;
+            pair[1] = right;
+            written += 1;
+        }
+        *count = written as f32;
+    }
+}
+
+impl Effect for CorrelationMeter {
+    type Shared = CorrelationShared;
+
+    fn create(_state: &UnityAudioEffectState_Data) -> Self {
+        CorrelationMeter {
+            param: [300.0, 4.0],
+            averages: Averages::default(),
+            countdown: 0,
+            shared: Arc::new(CorrelationShared {
+                correlation: AtomicF32::default(),
+                balance: AtomicF32::default(),
+                points: RingBuffer::new(SCOPE_POINTS),
+            }),
+        }
+    }
+
+    fn shared(&self) -> Arc<CorrelationShared> {
+        self.shared.clone()
+    }
+
+    fn parameters(&mut self) -> &mut [f32] {
+        &mut self.param
+    }
+
+    fn process(&mut self, context: &ProcessContext, input: Interleaved, mut output: InterleavedMut) {
+        remix(&input, &mut output);
+        if output.channels() == 0 {
+            return;
+        }
+
+        let coeff = time_coefficient(self.param[PARAM_WINDOW], context.samplerate as f32);
+        let decimation = (self.param[PARAM_DECIMATION].round() as usize).max(1);
+        let smooth = |average: &mut f32, value: f32| *average = value + coeff * (*average - value);
+
+        for frame in output.iter_frames_mut() {
+            let left = frame[0];
+            let right = frame.get(1).copied().unwrap_or(left);
+            let (mid, side) = (0.5 * (left + right), 0.5 * (left - right));
+
+            let a = &mut self.averages;
+            smooth(&mut a.left_right, left * right);
+            smooth(&mut a.left, left * left);
+            smooth(&mut a.right, right * right);
+            smooth(&mut a.mid, mid * mid);
+            smooth(&mut a.side, side * side);
+
+            if self.countdown == 0 {
+                // Dropped when full. The GUI polls every frame, so that only happens while it's hidden.
+                self.shared.points.push((left, right));
+                self.countdown = decimation;
+            }
+            self.countdown -= 1;
+        }
+
+        self.shared.correlation.store(self.averages.correlation());
+        self.shared.balance.store(self.averages.balance());
+    }
+
+    fn get_float_buffer(shared: &CorrelationShared, _parameters: &Parameters, name: &CStr, buffer: &mut [f32]) {
+        let value = match name.to_bytes() {
+            b"Correlation" => shared.correlation.load(),
+            b"Balance" => shared.balance.load(),
+            b"Vectorscope" => return shared.read_vectorscope(buffer),
+            _ => return,
+        };
+        if let Some(out) = buffer.first_mut() {
+            *out = value;
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use crate::audio_buffer::{remix, Interleaved, InterleavedMut};
+use crate::dsp::atomic::{AtomicBuffer, AtomicF32};
+use crate::dsp::fft::{self, FftAnalyzer};
+use crate::dsp::filter::BiquadFilter;
+use crate::effect::{Effect, Parameters, ProcessContext, MAX_CHANNELS};
+use crate::unity_audio_dsp::UnityAudioEffectState_Data;
+
+const PARAM_COUNT: usize = 20;
+const PARAM_MASTER_GAIN: usize = 0;
+const PARAM_LOW_SHELF_FREQ: usize = 1; // followed by gain and Q
+const PARAM_PEAK1_FREQ: usize = 4; // three peaking bands of freq, gain and Q each
+const PARAM_HIGH_SHELF_FREQ: usize = 13; // followed by gain and Q
+const PARAM_HP_FREQ: usize = 16;
+const PARAM_HP_Q: usize = 17;
+const PARAM_LP_FREQ: usize = 18;
+const PARAM_LP_Q: usize = 19;
+
+const PEAK_BANDS: usize = 3;
+
+/// Low-pass frequencies at or above this are treated as "off", matching the parameter's maximum.
+const LP_OFF_FREQ: f32 = 22000.0;
+
+/// High-pass, low shelf, three peaks, high shelf, low-pass.
+const NUM_FILTERS: usize = 7;
+
+const SPECTRUM_SIZE: usize = 4096;
+const SPECTRUM_DECAY: f32 = 0.9;
+
+/// Lowest and highest frequency of the "Response" curve, spaced logarithmically.
+const RESPONSE_MIN_FREQ: f32 = 20.0;
+const RESPONSE_MAX_FREQ: f32 = 20000.0;
+
+pub struct Equalizer {
+    param: [f32; PARAM_COUNT],
+    samplerate: f32,
+    dirty: bool,
+    /// Filter state for each channel; channels beyond `MAX_CHANNELS` pass through unfiltered.
+    filters: [[BiquadFilter; NUM_FILTERS]; MAX_CHANNELS],
+    analyzer: FftAnalyzer,
+    shared: Arc<EqualizerShared>,
+}
+
+pub struct EqualizerShared {
+    /// The response curve is drawn for the rate the filters actually run at.
+    samplerate: AtomicF32,
+    spectrum: AtomicBuffer,
+}
+
+/// Builds the filter cascade for parameters `p`. Disabled filters stay pass-through.
+fn design(p: &[f32; PARAM_COUNT], fs: f32) -> [BiquadFilter; NUM_FILTERS] {
+    let nyquist_guard = 0.49 * fs;
+    let mut filters = [BiquadFilter::default(); NUM_FILTERS];
+
+    if p[PARAM_HP_FREQ] > 0.0 {
+        filters[0].setup_highpass(p[PARAM_HP_FREQ].min(nyquist_guard), fs, p[PARAM_HP_Q]);
+    }
+
+    let band = |freq: usize| (p[freq].min(nyquist_guard), p[freq + 1], p[freq + 2]);
+
+    let (freq, gain, q) = band(PARAM_LOW_SHELF_FREQ);
+    filters[1].setup_low_shelf(freq, fs, gain, q);
+
+    for i in 0..PEAK_BANDS {
+        let (freq, gain, q) = band(PARAM_PEAK1_FREQ + 3 * i);
+        filters[2 + i].setup_peaking(freq, fs, gain, q);
+    }
+
+    let (freq, gain, q) = band(PARAM_HIGH_SHELF_FREQ);
+    filters[5].setup_high_shelf(freq, fs, gain, q);
+
+    if p[PARAM_LP_FREQ] < LP_OFF_FREQ && p[PARAM_LP_FREQ] < nyquist_guard {
+        filters[6].setup_lowpass(p[PARAM_LP_FREQ], fs, p[PARAM_LP_Q]);
+    }
+
+    filters
+}
+
+/// Total response in dB at `buffer.len()` log-spaced frequencies from 20 Hz to 20 kHz.
+fn read_response(p: &[f32; PARAM_COUNT], samplerate: f32, buffer: &mut [f32]) {
+    let filters = design(p, samplerate);
+    let master = p[PARAM_MASTER_GAIN];
+    let max_freq = RESPONSE_MAX_FREQ.min(0.5 * samplerate);
+    let ratio = max_freq / RESPONSE_MIN_FREQ;
+    let last = buffer.len().saturating_sub(1).max(1) as f32;
+
+    for (n, out) in buffer.iter_mut().enumerate() {
+        let freq = RESPONSE_MIN_FREQ * ratio.powf(n as f32 / last);
+        let magnitude: f32 = filters.iter().map(|f| f.magnitude(freq, samplerate)).product();
+        *out = master + 20.0 * magnitude.max(1e-10).log10();
+    }
+}
+
+/// Output spectrum in dB relative to a full-scale sine, linearly spaced from DC to Nyquist.
+fn read_spectrum(spectrum: &AtomicBuffer, buffer: &mut [f32]) {
+    fft::resample(spectrum.len(), |bin| spectrum.load(bin), buffer);
+    let full_scale = 0.54 * SPECTRUM_SIZE as f32 * 0.5; // Hamming window gain times N/2
+    for value in buffer.iter_mut() {
+        *value = 20.0 * (*value / full_scale).max(1e-10).log10();
+    }
+}
+
+impl Effect for Equalizer {
+    type Shared = EqualizerShared;
+
+    fn create(state: &UnityAudioEffectState_Data) -> Self {
+        Equalizer {
+            param: [
+                0.0,
+                100.0, 0.0, 0.707,
+                300.0, 0.0, 1.0,
+                1000.0, 0.0, 1.0,
+                3000.0, 0.0, 1.0,
+                8000.0, 0.0, 0.707,
+                0.0, 0.707,
+                LP_OFF_FREQ, 0.707,
+            ],
+            samplerate: state.samplerate as f32,
+            dirty: true,
+            filters: [[BiquadFilter::default(); NUM_FILTERS]; MAX_CHANNELS],
+            analyzer: FftAnalyzer::new(SPECTRUM_SIZE),
+            shared: Arc::new(EqualizerShared {
+                samplerate: AtomicF32::new(state.samplerate as f32),
+                spectrum: AtomicBuffer::new(SPECTRUM_SIZE / 2),
+            }),
+        }
+    }
+
+    fn shared(&self) -> Arc<EqualizerShared> {
+        self.shared.clone()
+    }
+
+    fn parameters(&mut self) -> &mut [f32] {
+        &mut self.param
+    }
+
+    fn parameter_changed(&mut self, _index: usize) {
+        self.dirty = true;
+    }
+
+    fn process(&mut self, context: &ProcessContext, input: Interleaved, mut output: InterleavedMut) {
+        if self.dirty || self.samplerate != context.samplerate as f32 {
+            self.samplerate = context.samplerate as f32;
+            self.shared.samplerate.store(self.samplerate);
+            let design = design(&self.param, self.samplerate);
+            // Only the coefficients change; each channel keeps its own filter state.
+            for channel in self.filters.iter_mut() {
+                for (filter, designed) in channel.iter_mut().zip(&design) {
+                    filter.copy_coefficients(designed);
+                }
+            }
+            self.dirty = false;
+        }
+
+        remix(&input, &mut output);
+
+        let master = 10.0f32.powf(self.param[PARAM_MASTER_GAIN] / 20.0);
+        for frame in output.iter_frames_mut() {
+            for (sample, filters) in frame.iter_mut().zip(self.filters.iter_mut()) {
+                let mut x = *sample;
+                for filter in filters.iter_mut() {
+                    x = filter.process(x);
+                }
+                *sample = x * master;
+            }
+        }
+
+        self.analyzer.analyze(output.samples(), output.channels(), SPECTRUM_DECAY);
+        if self.analyzer.can_be_read() {
+            self.shared.spectrum.store_from(self.analyzer.spectrum());
+        }
+    }
+
+    fn get_float_buffer(shared: &EqualizerShared, parameters: &Parameters, name: &CStr, buffer: &mut [f32]) {
+        match name.to_bytes() {
+            b"Response" => {
+                let p = std::array::from_fn(|index| parameters.get(index).unwrap_or(0.0));
+                read_response(&p, shared.samplerate.load(), buffer);
+            }
+            b"Spectrum" => read_spectrum(&shared.spectrum, buffer),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use crate::audio_buffer::{remix, Interleaved, InterleavedMut};
+use crate::dsp::dynamics::{db_to_gain, gain_to_db, time_coefficient, Ballistics, GainReductionMeter, SidechainKey};
+use crate::effect::{Effect, Parameters, ProcessContext};
+use crate::unity_audio_dsp::UnityAudioEffectState_Data;
+
+const PARAM_COUNT: usize = 8;
+const PARAM_THRESHOLD: usize = 0;
+const PARAM_HYSTERESIS: usize = 1;
+const PARAM_ATTACK: usize = 2;
+const PARAM_HOLD: usize = 3;
+const PARAM_RELEASE: usize = 4;
+const PARAM_RANGE: usize = 5;
+const PARAM_RATIO: usize = 6;
+const PARAM_SIDECHAIN: usize = 7;
+
+/// Fall time of the level envelope, long enough to ride over the zero crossings of low notes.
+const DETECTOR_DECAY_MS: f32 = 20.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum GateState {
+    Closed,
+    Open,
+    /// Below the close threshold, but staying open for `remaining` more samples.
+    Hold { remaining: u32 },
+}
+
+/// Open/hold/close decisions for one level per sample.
+///
+/// The gate opens once the level reaches `open` and only starts closing when it falls below
+/// `close`, which sits `hysteresis` dB lower, so a level hovering around the threshold doesn't
+/// chatter. Hold keeps it open a little longer across short dips between words or hits.
+#[derive(Clone, Copy, Debug)]
+struct GateStateMachine {
+    state: GateState,
+    open: f32,
+    close: f32,
+    hold: u32,
+}
+
+impl GateStateMachine {
+    #[inline]
+    fn step(&mut self, level: f32) -> GateState {
+        self.state = match self.state {
+            GateState::Closed if level >= self.open => GateState::Open,
+            GateState::Closed => GateState::Closed,
+            GateState::Open | GateState::Hold { .. } if level >= self.close => GateState::Open,
+            GateState::Open if self.hold > 0 => GateState::Hold { remaining: self.hold },
+            GateState::Hold { remaining } if remaining > 1 => GateState::Hold { remaining: remaining - 1 },
+            GateState::Open | GateState::Hold { .. } => GateState::Closed,
+        };
+        self.state
+    }
+}
+
+pub struct Gate {
+    param: [f32; PARAM_COUNT],
+    samplerate: f32,
+    dirty: bool,
+    machine: GateStateMachine,
+    envelope: f32,
+    decay: f32,
+    /// Smoothed gain in dB. Rising is the gate's attack, falling its release.
+    gain: Ballistics,
+    meter: Arc<GainReductionMeter>,
+}
+
+impl Gate {
+    fn update(&mut self) {
+        let p = &self.param;
+        self.machine.open = p[PARAM_THRESHOLD];
+        self.machine.close = p[PARAM_THRESHOLD] - p[PARAM_HYSTERESIS];
+        self.machine.hold = (p[PARAM_HOLD] * 0.001 * self.samplerate) as u32;
+        self.decay = time_coefficient(DETECTOR_DECAY_MS, self.samplerate);
+        // Ballistics calls the downward time "attack"; for a gate that's closing, i.e. release.
+        self.gain.set_times(p[PARAM_RELEASE], p[PARAM_ATTACK], self.samplerate);
+    }
+}
+
+impl Effect for Gate {
+    type Shared = GainReductionMeter;
+
+    fn create(state: &UnityAudioEffectState_Data) -> Self {
+        let mut gain = Ballistics::default();
+        gain.reset(-80.0);
+        Gate {
+            param: [-40.0, 6.0, 1.0, 50.0, 100.0, -80.0, 50.0, 0.0],
+            samplerate: state.samplerate as f32,
+            dirty: true,
+            machine: GateStateMachine { state: GateState::Closed, open: 0.0, close: 0.0, hold: 0 },
+            envelope: 0.0,
+            decay: 0.0,
+            gain,
+            meter: Arc::default(),
+        }
+    }
+
+    fn shared(&self) -> Arc<GainReductionMeter> {
+        self.meter.clone()
+    }
+
+    fn parameters(&mut self) -> &mut [f32] {
+        &mut self.param
+    }
+
+    fn parameter_changed(&mut self, _index: usize) {
+        self.dirty = true;
+    }
+
+    fn process(&mut self, context: &ProcessContext, input: Interleaved, mut output: InterleavedMut) {
+        if self.dirty || self.samplerate != context.samplerate as f32 {
+            self.samplerate = context.samplerate as f32;
+            self.update();
+            self.dirty = false;
+        }
+
+        remix(&input, &mut output);
+
+        let range = self.param[PARAM_RANGE];
+        let slope = self.param[PARAM_RATIO].max(1.0) - 1.0;
+
+        let key = SidechainKey::new(&context.sidechain, self.param[PARAM_SIDECHAIN] >= 0.5);
+
+        let mut deepest = 0.0f32;
+        for (n, frame) in output.iter_frames_mut().enumerate() {
+            self.envelope = key.peak(n, frame).max(self.envelope * self.decay);
+            let level = gain_to_db(self.envelope);
+
+            // While closed the ratio expands downwards from the threshold; a high ratio
+            // drops straight to the range like a classic gate.
+            let target = match self.machine.step(level) {
+                GateState::Closed => (slope * (level - self.machine.open)).clamp(range, 0.0),
+                GateState::Open | GateState::Hold { .. } => 0.0,
+            };
+            let smoothed = self.gain.process(target);
+            deepest = deepest.min(smoothed);
+
+            let gain = db_to_gain(smoothed);
+            frame.iter_mut().for_each(|sample| *sample *= gain);
+        }
+        self.meter.update(deepest, self.gain.value());
+    }
+
+    fn get_float_buffer(meter: &GainReductionMeter, _parameters: &Parameters, name: &CStr, buffer: &mut [f32]) {
+        if name.to_bytes() == b"GainReduction" {
+            if let Some(out) = buffer.first_mut() {
+                *out = meter.take();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use GateState::{Closed, Hold, Open};
+
+    fn machine(hold: u32) -> GateStateMachine {
+        GateStateMachine { state: GateState::Closed, open: -40.0, close: -46.0, hold }
+    }
+
+    fn run(machine: &mut GateStateMachine, levels: &[f32]) -> Vec<GateState> {
+        levels.iter().map(|&level| machine.step(level)).collect()
+    }
+
+    #[test]
+    fn opens_at_threshold() {
+        let mut gate = machine(0);
+        assert_eq!(run(&mut gate, &[-60.0, -40.1, -40.0, -10.0]), [Closed, Closed, Open, Open]);
+    }
+
+    #[test]
+    fn hysteresis_band() {
+        let mut gate = machine(0);
+        // Inside the band a closed gate stays closed and an open one stays open.
+        assert_eq!(run(&mut gate, &[-43.0, -45.9]), [Closed, Closed]);
+        assert_eq!(run(&mut gate, &[-39.0, -43.0, -45.9, -46.0]), [Open, Open, Open, Open]);
+        assert_eq!(run(&mut gate, &[-46.1, -43.0, -41.0, -40.0]), [Closed, Closed, Closed, Open]);
+    }
+
+    #[test]
+    fn hold_counts_down_to_closed() {
+        let mut gate = machine(3);
+        let states = run(&mut gate, &[-30.0, -50.0, -50.0, -50.0, -50.0, -50.0]);
+        let held = [Hold { remaining: 3 }, Hold { remaining: 2 }, Hold { remaining: 1 }];
+        assert_eq!(states, [&[Open][..], &held, &[Closed, Closed]].concat());
+    }
+
+    #[test]
+    fn reopens_during_hold() {
+        let mut gate = machine(3);
+        let states = run(&mut gate, &[-30.0, -50.0, -50.0, -44.0, -50.0]);
+        // Getting back above the close threshold is enough; the hold then restarts in full.
+        let expected = [Open, Hold { remaining: 3 }, Hold { remaining: 2 }, Open, Hold { remaining: 3 }];
+        assert_eq!(states, expected);
+    }
+
+    #[test]
+    fn zero_hold_closes_immediately() {
+        let mut gate = machine(0);
+        assert_eq!(run(&mut gate, &[-30.0, -47.0, -30.0, -50.0]), [Open, Closed, Open, Closed]);
+    }
+}
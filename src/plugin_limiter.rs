@@ -0,0 +1,354 @@
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use crate::audio_buffer::{remix, Interleaved, InterleavedMut};
+use crate::dsp::dynamics::{db_to_gain, gain_to_db, time_coefficient, GainReductionMeter};
+use crate::dsp::history::HistoryBuffer;
+use crate::dsp::true_peak::{self, TruePeakDetector};
+use crate::effect::{Effect, Parameters, ProcessContext, MAX_CHANNELS};
+use crate::unity_audio_dsp::UnityAudioEffectState_Data;
+
+const PARAM_COUNT: usize = 4;
+const PARAM_INPUT_GAIN: usize = 0;
+const PARAM_CEILING: usize = 1;
+const PARAM_RELEASE: usize = 2;
+const PARAM_LOOKAHEAD: usize = 3;
+
+/// Matches the "Lookahead" parameter's maximum.
+const MAX_LOOKAHEAD_MS: f32 = 10.0;
+
+/// Running minimum over the last `window` values, as a monotonic queue in preallocated storage.
+struct SlidingMinimum {
+    entries: Vec<(usize, f32)>,
+    front: usize,
+    len: usize,
+    window: usize,
+    time: usize,
+}
+
+impl SlidingMinimum {
+    fn new(capacity: usize) -> Self {
+        Self { entries: vec![(0, 0.0); capacity], front: 0, len: 0, window: 1, time: 0 }
+    }
+
+    /// Entries are timestamped, so the queue stays valid across a change: a shorter window just
+    /// evicts more on the next `process`.
+    fn set_window(&mut self, window: usize) {
+        self.window = window.clamp(1, self.entries.len());
+    }
+
+    #[inline]
+    fn process(&mut self, value: f32) -> f32 {
+        let capacity = self.entries.len();
+        // Older entries that aren't smaller can never be the minimum again.
+        while self.len > 0 && self.entries[(self.front + self.len - 1) % capacity].1 >= value {
+            self.len -= 1;
+        }
+        self.entries[(self.front + self.len) % capacity] = (self.time, value);
+        self.len += 1;
+        while self.entries[self.front].0 + self.window <= self.time {
+            self.front = (self.front + 1) % capacity;
+            self.len -= 1;
+        }
+        self.time += 1;
+        self.entries[self.front].1
+    }
+}
+
+/// Box filter over the last `window` values.
+struct MovingAverage {
+    values: Vec<f32>,
+    window: usize,
+    index: usize,
+    sum: f64,
+}
+
+impl MovingAverage {
+    fn new(capacity: usize) -> Self {
+        Self { values: vec![1.0; capacity], window: 1, index: 0, sum: 1.0 }
+    }
+
+    /// Keeps the newest values across a change so reductions already on their way aren't lost.
+    /// A longer window repeats the oldest kept value to fill up.
+    fn set_window(&mut self, window: usize) {
+        let (old, new) = (self.window, window.clamp(1, self.values.len()));
+        // Oldest first, then line the newest up with the end of the new window.
+        self.values[..old].rotate_left(self.index);
+        if new < old {
+            self.values.copy_within(old - new..old, 0);
+        } else {
+            self.values.copy_within(0..old, new - old);
+            let oldest = self.values[new - old];
+            self.values[..new - old].fill(oldest);
+        }
+        self.window = new;
+        self.index = 0;
+        self.sum = self.values[..new].iter().map(|&value| value as f64).sum();
+    }
+
+    #[inline]
+    fn process(&mut self, value: f32) -> f32 {
+        self.sum += value as f64 - self.values[self.index] as f64;
+        self.values[self.index] = value;
+        self.index = if self.index + 1 == self.window { 0 } else { self.index + 1 };
+        (self.sum / self.window as f64) as f32
+    }
+}
+
+/// Lookahead brickwall limiter working on true (inter-sample) peaks.
+///
+/// Each sample gets the gain that would bring its true peak down to the ceiling. A sliding minimum
+/// over the lookahead window holds that gain early enough, and a box filter of the same length
+/// turns the step into a ramp that is fully down by the time the peak leaves the delay line. Both
+/// only ever lower the gain, so the ramp can't overshoot; release then lets it back up slowly.
+pub struct Limiter {
+    param: [f32; PARAM_COUNT],
+    samplerate: f32,
+    /// In samples; 0 until the first `set_lookahead`.
+    lookahead: usize,
+    detectors: [TruePeakDetector; MAX_CHANNELS],
+    delay: Vec<HistoryBuffer>,
+    minimum: SlidingMinimum,
+    ramp: MovingAverage,
+    gain: f32,
+    meter: Arc<GainReductionMeter>,
+}
+
+impl Limiter {
+    fn set_lookahead(&mut self, samplerate: u32) {
+        self.samplerate = samplerate as f32;
+        let lookahead = (self.param[PARAM_LOOKAHEAD] * 0.001 * self.samplerate) as usize;
+        let lookahead = lookahead.clamp(1, self.ramp.values.len());
+        // Unity re-sends unchanged parameters; restarting the windows then would drop reductions
+        // queued for peaks still in the delay line.
+        if lookahead == self.lookahead {
+            return;
+        }
+        self.lookahead = lookahead;
+        // One more than the ramp length covers the peak's sample itself, and one more again the
+        // inter-sample stretch leading into it, which the detector reports a sample earlier.
+        self.minimum.set_window(self.lookahead + 2);
+        self.ramp.set_window(self.lookahead);
+    }
+}
+
+impl Effect for Limiter {
+    type Shared = GainReductionMeter;
+
+    fn create(state: &UnityAudioEffectState_Data) -> Self {
+        let max_lookahead = (MAX_LOOKAHEAD_MS * 0.001 * state.samplerate as f32) as usize + 1;
+        let delay_len = max_lookahead + true_peak::LATENCY + 4;
+        let mut limiter = Limiter {
+            param: [0.0, -1.0, 100.0, 2.0],
+            samplerate: state.samplerate as f32,
+            lookahead: 0,
+            detectors: [TruePeakDetector::new(); MAX_CHANNELS],
+            delay: (0..MAX_CHANNELS).map(|_| HistoryBuffer::new(delay_len)).collect(),
+            // The window goes up to `max_lookahead + 2`, and the queue briefly holds one entry more
+            // than that before evicting the oldest.
+            minimum: SlidingMinimum::new(max_lookahead + 3),
+            ramp: MovingAverage::new(max_lookahead),
+            gain: 1.0,
+            meter: Arc::default(),
+        };
+        limiter.set_lookahead(state.samplerate);
+        limiter
+    }
+
+    fn shared(&self) -> Arc<GainReductionMeter> {
+        self.meter.clone()
+    }
+
+    fn parameters(&mut self) -> &mut [f32] {
+        &mut self.param
+    }
+
+    fn parameter_changed(&mut self, index: usize) {
+        if index == PARAM_LOOKAHEAD {
+            self.set_lookahead(self.samplerate as u32);
+        }
+    }
+
+    fn process(&mut self, context: &ProcessContext, input: Interleaved, mut output: InterleavedMut) {
+        if self.samplerate != context.samplerate as f32 {
+            self.set_lookahead(context.samplerate);
+        }
+
+        remix(&input, &mut output);
+
+        let input_gain = db_to_gain(self.param[PARAM_INPUT_GAIN]);
+        let ceiling = db_to_gain(self.param[PARAM_CEILING]);
+        let release = time_coefficient(self.param[PARAM_RELEASE], self.samplerate);
+        let delay = self.lookahead + true_peak::LATENCY;
+
+        let mut deepest = 1.0f32;
+        for frame in output.iter_frames_mut() {
+            let mut peak = 0.0f32;
+            for ((sample, detector), line) in frame.iter_mut().zip(&mut self.detectors).zip(&mut self.delay) {
+                *sample *= input_gain;
+                peak = peak.max(detector.process(*sample));
+                line.feed(*sample);
+            }
+
+            let required = if peak > ceiling { ceiling / peak } else { 1.0 };
+            let target = self.ramp.process(self.minimum.process(required));
+            self.gain = if target < self.gain { target } else { target + release * (self.gain - target) };
+            deepest = deepest.min(self.gain);
+
+            for (n, sample) in frame.iter_mut().enumerate() {
+                let delayed = match self.delay.get(n) {
+                    Some(line) => line.read(delay) * self.gain,
+                    // Channels past MAX_CHANNELS aren't delayed or detected; clipping still holds them.
+                    None => *sample,
+                };
+                // The gain already keeps samples below the ceiling; the clamp only absorbs rounding.
+                *sample = delayed.clamp(-ceiling, ceiling);
+            }
+        }
+        self.meter.update(gain_to_db(deepest), gain_to_db(self.gain));
+    }
+
+    fn get_float_buffer(meter: &GainReductionMeter, _parameters: &Parameters, name: &CStr, buffer: &mut [f32]) {
+        if name.to_bytes() == b"GainReduction" {
+            if let Some(out) = buffer.first_mut() {
+                *out = meter.take();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effect::testing;
+
+    const SAMPLERATE: u32 = 48000;
+
+    /// Limits `input` and returns the output plus its largest sample and largest true peak.
+    fn limit(param: [f32; PARAM_COUNT], input: &[f32]) -> (Vec<f32>, f32, f32) {
+        let mut limiter = Limiter::create(&testing::state(SAMPLERATE));
+        for (index, value) in param.into_iter().enumerate() {
+            limiter.param[index] = value;
+            limiter.parameter_changed(index);
+        }
+        let mut output = vec![0.0; input.len()];
+        for (input, output) in input.chunks(512).zip(output.chunks_mut(512)) {
+            testing::process(&mut limiter, SAMPLERATE, input, output, 1);
+        }
+        let mut detector = TruePeakDetector::new();
+        let sample_peak = output.iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+        let true_peak = output.iter().fold(0.0f32, |peak, &x| peak.max(detector.process(x)));
+        (output, sample_peak, true_peak)
+    }
+
+    /// Bursts of a loud, bright tone out of silence, each starting on its largest sample; the tests
+    /// push them a further 12 dB with the input gain.
+    fn bursts() -> Vec<f32> {
+        let burst = 2400;
+        (0..SAMPLERATE as usize)
+            .map(|n| match n % (4 * burst) {
+                n if n < burst => 0.9 * (0.3 * n as f32).cos() + 0.1 * (2.9 * n as f32).sin(),
+                _ => 0.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn catches_intersample_peaks() {
+        // A quarter-rate sine sampled 45 degrees off its crests: every sample sits 3 dB below the
+        // true peak, so a sample-peak limiter would let the full 0 dBTP through.
+        let input: Vec<f32> = (0..SAMPLERATE as usize)
+            .map(|n| (std::f32::consts::FRAC_PI_2 * n as f32 + std::f32::consts::FRAC_PI_4).sin())
+            .collect();
+        assert!(input.iter().all(|x| x.abs() < db_to_gain(-2.9)));
+
+        let ceiling = db_to_gain(-1.0);
+        let (output, sample_peak, true_peak) = limit([0.0, -1.0, 100.0, 2.0], &input);
+        assert!(sample_peak <= ceiling, "sample peak {}", gain_to_db(sample_peak));
+        // The ramp must have brought the gain down on its own: clamping samples that never exceed
+        // the ceiling would leave the true peak untouched.
+        assert!(true_peak <= ceiling * db_to_gain(0.05), "true peak {} dBTP", gain_to_db(true_peak));
+        // And it shouldn't overdo it once settled either.
+        let settled = output[SAMPLERATE as usize / 2..].iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+        assert!(settled > ceiling * db_to_gain(-3.5), "settled at {} dB", gain_to_db(settled));
+    }
+
+    #[test]
+    fn holds_ceiling_on_transients() {
+        let input = bursts();
+        for lookahead in [0.5, 2.0, 10.0] {
+            let ceiling_db = -0.3;
+            let ceiling = db_to_gain(ceiling_db);
+            let (output, sample_peak, true_peak) = limit([12.0, ceiling_db, 50.0, lookahead], &input);
+            assert!(sample_peak <= ceiling, "{lookahead} ms: sample peak {}", gain_to_db(sample_peak));
+            assert!(
+                true_peak <= ceiling * db_to_gain(0.05),
+                "{lookahead} ms: true peak {} dBTP",
+                gain_to_db(true_peak)
+            );
+            // Clipping a burst flat would park many samples right on the ceiling; the gain ramp
+            // only ever grazes it.
+            let clipped = output.iter().filter(|x| x.abs() >= ceiling * db_to_gain(-0.01)).count();
+            assert!(clipped < 8, "{lookahead} ms: {clipped} samples at the ceiling");
+        }
+    }
+
+    /// Unity re-sends parameters that haven't changed. Restarting the windows on each re-send of
+    /// "Lookahead" would lose the reductions queued for peaks already in the delay line and leave
+    /// the final clamp to flatten them.
+    #[test]
+    fn resending_lookahead_keeps_pending_reductions() {
+        let input = bursts();
+        let param = [12.0, -0.3, 50.0, 5.0];
+        let ceiling = db_to_gain(param[PARAM_CEILING]);
+        let (expected, _, _) = limit(param, &input);
+
+        let mut limiter = Limiter::create(&testing::state(SAMPLERATE));
+        for (index, value) in param.into_iter().enumerate() {
+            limiter.param[index] = value;
+            limiter.parameter_changed(index);
+        }
+        let mut output = vec![0.0; input.len()];
+        for (input, output) in input.chunks(256).zip(output.chunks_mut(256)) {
+            limiter.parameter_changed(PARAM_LOOKAHEAD);
+            testing::process(&mut limiter, SAMPLERATE, input, output, 1);
+        }
+
+        // The clamp leaves samples exactly on the ceiling; the gain alone doesn't.
+        let clamped = output.iter().filter(|x| x.abs() >= ceiling).count();
+        assert_eq!(clamped, 0, "{clamped} samples clamped");
+        assert!(output == expected, "re-sending an unchanged lookahead changed the output");
+    }
+
+    /// A real change keeps the newest values of the ramp in order and its sum in step with them.
+    #[test]
+    fn moving_average_resizes_in_place() {
+        let mut average = MovingAverage::new(8);
+        average.set_window(4);
+        for value in [1.0, 2.0, 3.0, 4.0, 5.0, 6.0] {
+            average.process(value);
+        }
+        average.set_window(2);
+        assert_eq!(&average.values[..2], [5.0, 6.0]);
+        assert_eq!(average.process(7.0), 6.5);
+        average.set_window(5);
+        assert_eq!(&average.values[..5], [6.0, 6.0, 6.0, 6.0, 7.0]);
+        assert_eq!(average.process(8.0), 6.6);
+    }
+
+    #[test]
+    fn sliding_minimum_survives_its_largest_window() {
+        let mut limiter = Limiter::create(&testing::state(SAMPLERATE));
+        limiter.param[PARAM_LOOKAHEAD] = MAX_LOOKAHEAD_MS;
+        // A host running faster than at creation clamps the lookahead to the storage.
+        limiter.set_lookahead(2 * SAMPLERATE);
+        assert_eq!(limiter.lookahead, limiter.ramp.values.len());
+        // Rising values all stay queued, filling it to the window length; the minimum is then
+        // always the value that is about to age out.
+        let window = limiter.minimum.window;
+        for n in 0..4 * window {
+            let expected = n.saturating_sub(window - 1) as f32;
+            assert_eq!(limiter.minimum.process(n as f32), expected, "sample {n}");
+        }
+    }
+}
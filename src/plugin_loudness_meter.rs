@@ -0,0 +1,497 @@
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use crate::audio_buffer::{remix, Interleaved, InterleavedMut};
+use crate::dsp::atomic::AtomicF32;
+use crate::dsp::dynamics::gain_to_db;
+use crate::dsp::filter::BiquadFilter;
+use crate::dsp::true_peak::TruePeakDetector;
+use crate::effect::{Effect, Parameters, ProcessContext, MAX_CHANNELS};
+use crate::unity_audio_dsp::UnityAudioEffectState_Data;
+
+const PARAM_COUNT: usize = 2;
+const PARAM_RESET: usize = 0;
+const PARAM_INDEX: usize = 1;
+
+/// Measurements are built from 100 ms sub-blocks: the momentary window is 4 of them, short-term 30.
+const MOMENTARY_SUB_BLOCKS: usize = 4;
+const SHORT_TERM_SUB_BLOCKS: usize = 30;
+
+const ABSOLUTE_GATE: f64 = -70.0;
+const INTEGRATED_RELATIVE_GATE: f64 = -10.0;
+const RANGE_RELATIVE_GATE: f64 = -20.0;
+
+/// Gated measurements are binned at 0.1 LU from the absolute gate up to +10 LUFS, so integrated
+/// loudness and loudness range can run for hours without storing every block.
+const HISTOGRAM_STEP: f64 = 0.1;
+const HISTOGRAM_BINS: usize = 800;
+
+/// What `loudness` and `gain_to_db` make of silence.
+const SILENCE_LUFS: f32 = -200.691;
+const SILENCE_DBTP: f32 = -200.0;
+
+/// Number of instances script can tell apart, matching the "Index" parameter's range.
+pub const SLOTS: usize = 64;
+
+/// Latest readings of each instance for script.
+static PUBLISHED: [LoudnessReadings; SLOTS] = [const { LoudnessReadings::new() }; SLOTS];
+
+/// Readings last published to `index`; silence for an unused or out-of-range slot.
+pub fn published(index: i32) -> &'static LoudnessReadings {
+    static UNUSED: LoudnessReadings = LoudnessReadings::new();
+    usize::try_from(index).ok().and_then(|i| PUBLISHED.get(i)).unwrap_or(&UNUSED)
+}
+
+/// BS.1770 loudness of a weighted mean square.
+fn loudness(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.max(1e-20).log10()
+}
+
+/// Channel weights for Unity's speaker layouts: surrounds count 1.41x, the LFE not at all.
+fn channel_weight(channel: usize, channels: usize) -> f64 {
+    match (channels, channel) {
+        (6 | 8, 3) => 0.0,
+        (6 | 8, 4..) | (4, 2 | 3) => 1.41,
+        _ => 1.0,
+    }
+}
+
+/// The two K-weighting stages of BS.1770 for any sample rate: a +4 dB high shelf modelling the
+/// head, then the "RLB" high-pass. Derived from the analog prototypes of the 48 kHz reference.
+fn k_weighting(samplerate: f32) -> [BiquadFilter; 2] {
+    let fs = samplerate as f64;
+    let mut stages = [BiquadFilter::default(); 2];
+
+    let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let vh = 10.0f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    stages[0].set_coefficients(
+        [(vh + vb * k / q + k * k) as f32, (2.0 * (k * k - vh)) as f32, (vh - vb * k / q + k * k) as f32],
+        [(1.0 + k / q + k * k) as f32, (2.0 * (k * k - 1.0)) as f32, (1.0 - k / q + k * k) as f32],
+    );
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let a0 = 1.0 + k / q + k * k;
+    // The reference normalizes the numerator to [1, -2, 1] rather than by a0.
+    stages[1].set_coefficients(
+        [a0 as f32, (-2.0 * a0) as f32, a0 as f32],
+        [a0 as f32, (2.0 * (k * k - 1.0)) as f32, (1.0 - k / q + k * k) as f32],
+    );
+
+    stages
+}
+
+/// Count and summed energy of the gated blocks falling into each 0.1 LU bin.
+struct LoudnessHistogram {
+    bins: Vec<(u32, f64)>,
+}
+
+impl LoudnessHistogram {
+    fn new() -> Self {
+        Self { bins: vec![(0, 0.0); HISTOGRAM_BINS] }
+    }
+
+    fn clear(&mut self) {
+        self.bins.fill((0, 0.0));
+    }
+
+    /// Adds a block, dropping it if it's below the absolute gate.
+    fn add(&mut self, energy: f64) {
+        let position = (loudness(energy) - ABSOLUTE_GATE) / HISTOGRAM_STEP;
+        if position >= 0.0 {
+            let bin = &mut self.bins[(position as usize).min(HISTOGRAM_BINS - 1)];
+            bin.0 += 1;
+            bin.1 += energy;
+        }
+    }
+
+    fn bin_loudness(bin: usize) -> f64 {
+        ABSOLUTE_GATE + (bin as f64 + 0.5) * HISTOGRAM_STEP
+    }
+
+    /// The bins at or above `offset` LU relative to the mean of everything above the absolute gate.
+    fn gated(&self, offset: f64) -> Option<&[(u32, f64)]> {
+        let (count, energy) = self.bins.iter().fold((0u64, 0.0), |(n, e), bin| (n + bin.0 as u64, e + bin.1));
+        if count == 0 {
+            return None;
+        }
+        let gate = loudness(energy / count as f64) + offset;
+        let first = ((gate - ABSOLUTE_GATE) / HISTOGRAM_STEP).round().max(0.0) as usize;
+        Some(&self.bins[first.min(HISTOGRAM_BINS)..])
+    }
+
+    /// Gated mean loudness, for integrated loudness.
+    fn integrated(&self) -> Option<f64> {
+        let bins = self.gated(INTEGRATED_RELATIVE_GATE)?;
+        let (count, energy) = bins.iter().fold((0u64, 0.0), |(n, e), bin| (n + bin.0 as u64, e + bin.1));
+        (count > 0).then(|| loudness(energy / count as f64))
+    }
+
+    /// Spread between the 10th and 95th percentile of the gated blocks, for loudness range.
+    fn range(&self) -> Option<f64> {
+        let bins = self.gated(RANGE_RELATIVE_GATE)?;
+        let offset = HISTOGRAM_BINS - bins.len();
+        let count: u64 = bins.iter().map(|bin| bin.0 as u64).sum();
+        let percentile = |p: f64| {
+            let target = (p * (count.saturating_sub(1)) as f64) as u64;
+            let mut seen = 0;
+            for (i, bin) in bins.iter().enumerate() {
+                seen += bin.0 as u64;
+                if seen > target {
+                    return Self::bin_loudness(offset + i);
+                }
+            }
+            Self::bin_loudness(HISTOGRAM_BINS - 1)
+        };
+        (count > 0).then(|| percentile(0.95) - percentile(0.10))
+    }
+}
+
+/// EBU R128 loudness meter: momentary, short-term and integrated loudness in LUFS, loudness range
+/// in LU and true peak in dBTP, each readable as a one-value float buffer of the same name and from
+/// script through the `RustyLoudnessMeterGet*` exports.
+pub struct LoudnessMeter {
+    param: [f32; PARAM_COUNT],
+    samplerate: f32,
+    /// "Reset" as last seen; a reset only happens when it goes from below 0.5 to at or above it.
+    reset_level: f32,
+    filters: [[BiquadFilter; 2]; MAX_CHANNELS],
+    peaks: [TruePeakDetector; MAX_CHANNELS],
+    true_peak: f32,
+    sub_block_len: usize,
+    sub_block_pos: usize,
+    sub_block_sum: f64,
+    /// Mean weighted energy of the most recent sub-blocks, as a ring.
+    sub_blocks: [f64; SHORT_TERM_SUB_BLOCKS],
+    sub_block_index: usize,
+    sub_blocks_seen: usize,
+    momentary: f64,
+    short_term: f64,
+    blocks: LoudnessHistogram,
+    short_term_blocks: LoudnessHistogram,
+    readings: Arc<LoudnessReadings>,
+}
+
+/// What the meter shows, in LUFS, LU and dBTP. Loudness reads -200 LUFS until there's something to
+/// measure.
+pub struct LoudnessReadings {
+    pub momentary: AtomicF32,
+    pub short_term: AtomicF32,
+    pub integrated: AtomicF32,
+    pub range: AtomicF32,
+    pub true_peak: AtomicF32,
+}
+
+impl LoudnessReadings {
+    const fn new() -> Self {
+        Self {
+            momentary: AtomicF32::new(SILENCE_LUFS),
+            short_term: AtomicF32::new(SILENCE_LUFS),
+            integrated: AtomicF32::new(SILENCE_LUFS),
+            range: AtomicF32::new(0.0),
+            true_peak: AtomicF32::new(SILENCE_DBTP),
+        }
+    }
+
+    fn copy_from(&self, other: &LoudnessReadings) {
+        self.momentary.store(other.momentary.load());
+        self.short_term.store(other.short_term.load());
+        self.integrated.store(other.integrated.load());
+        self.range.store(other.range.load());
+        self.true_peak.store(other.true_peak.load());
+    }
+}
+
+impl Default for LoudnessReadings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoudnessMeter {
+    fn set_samplerate(&mut self, samplerate: f32) {
+        self.samplerate = samplerate;
+        let stages = k_weighting(samplerate);
+        for filters in self.filters.iter_mut() {
+            *filters = stages;
+        }
+        self.sub_block_len = (samplerate as usize / 10).max(1);
+        self.sub_block_pos = 0;
+        self.sub_block_sum = 0.0;
+    }
+
+    fn reset(&mut self) {
+        self.blocks.clear();
+        self.short_term_blocks.clear();
+        self.true_peak = 0.0;
+        self.publish_gated();
+        self.readings.true_peak.store(SILENCE_DBTP);
+    }
+
+    /// Integrated loudness and range only move once per sub-block, so they're worked out here
+    /// rather than each time the GUI asks.
+    fn publish_gated(&self) {
+        self.readings.integrated.store(self.blocks.integrated().map_or(SILENCE_LUFS, |lufs| lufs as f32));
+        self.readings.range.store(self.short_term_blocks.range().unwrap_or(0.0) as f32);
+    }
+
+    fn end_sub_block(&mut self) {
+        self.sub_blocks[self.sub_block_index] = self.sub_block_sum / self.sub_block_len as f64;
+        self.sub_block_index = (self.sub_block_index + 1) % SHORT_TERM_SUB_BLOCKS;
+        self.sub_blocks_seen += 1;
+        self.sub_block_pos = 0;
+        self.sub_block_sum = 0.0;
+
+        let recent = |count: usize| {
+            let sum: f64 = (1..=count)
+                .map(|age| self.sub_blocks[(self.sub_block_index + SHORT_TERM_SUB_BLOCKS - age) % SHORT_TERM_SUB_BLOCKS])
+                .sum();
+            sum / count as f64
+        };
+        self.momentary = recent(MOMENTARY_SUB_BLOCKS);
+        self.short_term = recent(SHORT_TERM_SUB_BLOCKS);
+
+        // Gating blocks overlap by 75%, i.e. one completes every sub-block; short-term values for
+        // the range are sampled at the same 10 Hz.
+        if self.sub_blocks_seen >= MOMENTARY_SUB_BLOCKS {
+            self.blocks.add(self.momentary);
+        }
+        if self.sub_blocks_seen >= SHORT_TERM_SUB_BLOCKS {
+            self.short_term_blocks.add(self.short_term);
+        }
+
+        self.readings.momentary.store(loudness(self.momentary) as f32);
+        self.readings.short_term.store(loudness(self.short_term) as f32);
+        self.publish_gated();
+    }
+}
+
+impl Effect for LoudnessMeter {
+    type Shared = LoudnessReadings;
+
+    fn create(state: &UnityAudioEffectState_Data) -> Self {
+        let mut meter = LoudnessMeter {
+            param: [0.0, 0.0],
+            samplerate: state.samplerate as f32,
+            reset_level: 0.0,
+            filters: [[BiquadFilter::default(); 2]; MAX_CHANNELS],
+            peaks: [TruePeakDetector::new(); MAX_CHANNELS],
+            true_peak: 0.0,
+            sub_block_len: 1,
+            sub_block_pos: 0,
+            sub_block_sum: 0.0,
+            sub_blocks: [0.0; SHORT_TERM_SUB_BLOCKS],
+            sub_block_index: 0,
+            sub_blocks_seen: 0,
+            momentary: 0.0,
+            short_term: 0.0,
+            blocks: LoudnessHistogram::new(),
+            short_term_blocks: LoudnessHistogram::new(),
+            readings: Arc::default(),
+        };
+        meter.set_samplerate(state.samplerate as f32);
+        meter
+    }
+
+    fn shared(&self) -> Arc<LoudnessReadings> {
+        self.readings.clone()
+    }
+
+    fn parameters(&mut self) -> &mut [f32] {
+        &mut self.param
+    }
+
+    fn parameter_changed(&mut self, index: usize) {
+        // Unity re-sends every parameter on scene load and snapshot changes, so only a rising edge
+        // counts as asking for a reset.
+        if index == PARAM_RESET {
+            let level = self.param[PARAM_RESET];
+            if self.reset_level < 0.5 && level >= 0.5 {
+                self.reset();
+            }
+            self.reset_level = level;
+        }
+    }
+
+    fn process(&mut self, context: &ProcessContext, input: Interleaved, mut output: InterleavedMut) {
+        if self.samplerate != context.samplerate as f32 {
+            self.set_samplerate(context.samplerate as f32);
+        }
+
+        remix(&input, &mut output);
+
+        let channels = output.channels();
+        let mut weights = [0.0; MAX_CHANNELS];
+        for (channel, weight) in weights.iter_mut().enumerate().take(channels) {
+            *weight = channel_weight(channel, channels);
+        }
+
+        for frame in output.iter_frames_mut() {
+            let mut energy = 0.0;
+            for (((sample, filters), peak), weight) in frame.iter().zip(&mut self.filters).zip(&mut self.peaks).zip(&weights) {
+                let weighted = filters.iter_mut().fold(*sample, |x, f| f.process(x)) as f64;
+                energy += weight * weighted * weighted;
+                self.true_peak = self.true_peak.max(peak.process(*sample));
+            }
+
+            self.sub_block_sum += energy;
+            self.sub_block_pos += 1;
+            if self.sub_block_pos == self.sub_block_len {
+                self.end_sub_block();
+            }
+        }
+        self.readings.true_peak.store(gain_to_db(self.true_peak));
+
+        if let Some(slot) = PUBLISHED.get(self.param[PARAM_INDEX].round().max(0.0) as usize) {
+            slot.copy_from(&self.readings);
+        }
+    }
+
+    fn get_float_buffer(readings: &LoudnessReadings, _parameters: &Parameters, name: &CStr, buffer: &mut [f32]) {
+        let value = match name.to_bytes() {
+            b"Momentary" => &readings.momentary,
+            b"ShortTerm" => &readings.short_term,
+            b"Integrated" => &readings.integrated,
+            b"Range" => &readings.range,
+            b"TruePeak" => &readings.true_peak,
+            _ => return,
+        };
+        if let Some(out) = buffer.first_mut() {
+            *out = value.load();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::dynamics::db_to_gain;
+    use crate::effect::testing;
+
+    const SAMPLERATE: u32 = 48000;
+
+    /// Feeds a 1 kHz stereo sine through `meter`, one `(dBFS, seconds)` segment after another, the
+    /// way the EBU Tech 3341 and 3342 test signals are built.
+    fn play(meter: &mut LoudnessMeter, segments: &[(f32, f32)]) {
+        let mut phase = 0.0f64;
+        let step = 2.0 * std::f64::consts::PI * 1000.0 / SAMPLERATE as f64;
+        let (mut input, mut output) = (vec![0.0; 2 * 4800], vec![0.0; 2 * 4800]);
+        for &(level, seconds) in segments {
+            let amplitude = db_to_gain(level);
+            let mut remaining = (seconds * SAMPLERATE as f32).round() as usize;
+            while remaining > 0 {
+                let frames = remaining.min(4800);
+                for frame in input[..2 * frames].chunks_exact_mut(2) {
+                    frame.fill(amplitude * phase.sin() as f32);
+                    phase = (phase + step) % (2.0 * std::f64::consts::PI);
+                }
+                testing::process(meter, SAMPLERATE, &input[..2 * frames], &mut output[..2 * frames], 2);
+                remaining -= frames;
+            }
+        }
+    }
+
+    fn measure(segments: &[(f32, f32)]) -> LoudnessMeter {
+        let mut meter = LoudnessMeter::create(&testing::state(SAMPLERATE));
+        play(&mut meter, segments);
+        meter
+    }
+
+    fn assert_near(name: &str, value: f32, expected: f32) {
+        // EBU Tech 3341 and 3342 allow ±0.1 LU; the last digit gives a little for f32 rounding.
+        assert!((value - expected).abs() <= 0.1 + 1e-4, "{name} {value}, expected {expected}");
+    }
+
+    /// Tech 3341 cases 1 and 2: a steady stereo sine reads its level in M, S and I.
+    #[test]
+    fn steady_sine() {
+        for level in [-23.0, -33.0] {
+            let meter = measure(&[(level, 20.0)]);
+            let readings = &meter.readings;
+            assert_near("momentary", readings.momentary.load(), level);
+            assert_near("short-term", readings.short_term.load(), level);
+            assert_near("integrated", readings.integrated.load(), level);
+        }
+    }
+
+    /// Tech 3341 cases 3 to 5: the gates keep quiet passages out of the integrated loudness.
+    #[test]
+    fn gated_integrated_loudness() {
+        let cases: [&[(f32, f32)]; 3] = [
+            &[(-36.0, 10.0), (-23.0, 60.0), (-36.0, 10.0)],
+            &[(-72.0, 10.0), (-36.0, 10.0), (-23.0, 60.0), (-36.0, 10.0), (-72.0, 10.0)],
+            &[(-26.0, 20.0), (-20.0, 20.1), (-26.0, 20.0)],
+        ];
+        for segments in cases {
+            assert_near("integrated", measure(segments).readings.integrated.load(), -23.0);
+        }
+    }
+
+    /// Tech 3342 cases 1 to 4.
+    #[test]
+    fn loudness_range() {
+        let cases: [(&[(f32, f32)], f32); 4] = [
+            (&[(-20.0, 20.0), (-30.0, 20.0)], 10.0),
+            (&[(-20.0, 20.0), (-15.0, 20.0)], 5.0),
+            (&[(-40.0, 20.0), (-20.0, 20.0)], 20.0),
+            (&[(-50.0, 20.0), (-35.0, 20.0), (-20.0, 20.0), (-35.0, 20.0), (-50.0, 20.0)], 15.0),
+        ];
+        for (segments, expected) in cases {
+            assert_near("range", measure(segments).readings.range.load(), expected);
+        }
+    }
+
+    fn set_reset(meter: &mut LoudnessMeter, value: f32) {
+        meter.param[PARAM_RESET] = value;
+        meter.parameter_changed(PARAM_RESET);
+    }
+
+    #[test]
+    fn reset_restarts_the_gated_measurements() {
+        let mut meter = measure(&[(-20.0, 10.0), (-30.0, 10.0)]);
+        set_reset(&mut meter, 1.0);
+        assert_eq!(meter.readings.integrated.load(), SILENCE_LUFS);
+        assert_eq!(meter.readings.range.load(), 0.0);
+        assert_eq!(meter.readings.true_peak.load(), SILENCE_DBTP);
+
+        play(&mut meter, &[(-33.0, 10.0)]);
+        assert_near("integrated", meter.readings.integrated.load(), -33.0);
+
+        // Another press needs the button released first.
+        set_reset(&mut meter, 0.0);
+        set_reset(&mut meter, 1.0);
+        assert_eq!(meter.readings.integrated.load(), SILENCE_LUFS);
+    }
+
+    /// Unity re-sends every parameter on scene load and snapshot changes; that mustn't wipe the
+    /// measurement.
+    #[test]
+    fn resending_reset_keeps_the_measurement() {
+        let mut meter = measure(&[(-23.0, 10.0)]);
+        for value in [0.0, 0.0] {
+            set_reset(&mut meter, value);
+        }
+        assert_near("integrated", meter.readings.integrated.load(), -23.0);
+
+        set_reset(&mut meter, 1.0);
+        play(&mut meter, &[(-23.0, 10.0)]);
+        for value in [1.0, 1.0] {
+            set_reset(&mut meter, value);
+        }
+        assert_near("integrated", meter.readings.integrated.load(), -23.0);
+        assert_near("true peak", meter.readings.true_peak.load(), -23.0);
+    }
+
+    #[test]
+    fn script_reads_the_instance_at_its_index() {
+        let mut meter = LoudnessMeter::create(&testing::state(SAMPLERATE));
+        meter.param[PARAM_INDEX] = 7.0;
+        play(&mut meter, &[(-23.0, 5.0)]);
+        assert_near("integrated", published(7).integrated.load(), -23.0);
+        assert_near("true peak", published(7).true_peak.load(), -23.0);
+        assert_eq!(published(-1).integrated.load(), SILENCE_LUFS);
+        assert_eq!(published(SLOTS as i32).integrated.load(), SILENCE_LUFS);
+    }
+}
@@ -0,0 +1,165 @@
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use crate::audio_buffer::{remix, Interleaved, InterleavedMut};
+use crate::dsp::crossover::{Crossover, MAX_BANDS};
+use crate::dsp::dynamics::{compress_db, db_to_gain, Ballistics, Detection, GainReductionMeter, LevelDetector};
+use crate::effect::{Effect, Parameters, ProcessContext, MAX_CHANNELS};
+use crate::unity_audio_dsp::UnityAudioEffectState_Data;
+
+const PARAM_COUNT: usize = 5 + 5 * MAX_BANDS;
+const PARAM_MASTER_GAIN: usize = 0;
+const PARAM_BANDS: usize = 1;
+const PARAM_CROSSOVER1: usize = 2; // one per split, ascending
+const PARAM_BAND1: usize = 5; // threshold, ratio, attack, release and makeup per band
+
+const THRESHOLD: usize = 0;
+const RATIO: usize = 1;
+const ATTACK: usize = 2;
+const RELEASE: usize = 3;
+const MAKEUP: usize = 4;
+
+pub struct Multiband {
+    param: [f32; PARAM_COUNT],
+    samplerate: f32,
+    dirty: bool,
+    /// Band splitters for each channel; channels beyond `MAX_CHANNELS` pass through uncompressed.
+    crossovers: [Crossover; MAX_CHANNELS],
+    /// Detection and gain are linked across channels so the stereo image doesn't wander.
+    detectors: [LevelDetector; MAX_BANDS],
+    envelopes: [Ballistics; MAX_BANDS],
+    meters: Arc<[GainReductionMeter; MAX_BANDS]>,
+}
+
+/// Number of bands in use for the "Bands" parameter value.
+fn band_count(param: f32) -> usize {
+    (param.round() as usize).clamp(3, MAX_BANDS)
+}
+
+impl Multiband {
+    fn band_param(&self, band: usize, which: usize) -> f32 {
+        self.param[PARAM_BAND1 + 5 * band + which]
+    }
+
+    fn update(&mut self) {
+        let bands = band_count(self.param[PARAM_BANDS]);
+        let fs = self.samplerate;
+
+        // Keep the crossovers ascending and below Nyquist whatever order the sliders are in.
+        let mut freqs = [0.0; MAX_BANDS - 1];
+        let mut lowest = 10.0f32;
+        for (i, freq) in freqs[..bands - 1].iter_mut().enumerate() {
+            *freq = self.param[PARAM_CROSSOVER1 + i].clamp(lowest, 0.45 * fs);
+            lowest = *freq;
+        }
+        for crossover in self.crossovers.iter_mut() {
+            crossover.setup(&freqs[..bands - 1], fs);
+        }
+
+        for band in 0..MAX_BANDS {
+            let (attack, release) = (self.band_param(band, ATTACK), self.band_param(band, RELEASE));
+            self.envelopes[band].set_times(attack, release, fs);
+        }
+    }
+}
+
+impl Effect for Multiband {
+    type Shared = [GainReductionMeter; MAX_BANDS];
+
+    fn create(state: &UnityAudioEffectState_Data) -> Self {
+        Multiband {
+            param: [
+                0.0, 3.0,
+                200.0, 2000.0, 8000.0,
+                -24.0, 2.0, 20.0, 200.0, 0.0,
+                -24.0, 2.0, 10.0, 150.0, 0.0,
+                -24.0, 2.0, 5.0, 100.0, 0.0,
+                -24.0, 2.0, 2.0, 80.0, 0.0,
+            ],
+            samplerate: state.samplerate as f32,
+            dirty: true,
+            crossovers: [Crossover::default(); MAX_CHANNELS],
+            detectors: [LevelDetector::new(Detection::Peak); MAX_BANDS],
+            envelopes: [Ballistics::default(); MAX_BANDS],
+            meters: Arc::default(),
+        }
+    }
+
+    fn shared(&self) -> Arc<[GainReductionMeter; MAX_BANDS]> {
+        self.meters.clone()
+    }
+
+    fn parameters(&mut self) -> &mut [f32] {
+        &mut self.param
+    }
+
+    fn parameter_changed(&mut self, _index: usize) {
+        self.dirty = true;
+    }
+
+    fn process(&mut self, context: &ProcessContext, input: Interleaved, mut output: InterleavedMut) {
+        if self.dirty || self.samplerate != context.samplerate as f32 {
+            self.samplerate = context.samplerate as f32;
+            self.update();
+            self.dirty = false;
+        }
+
+        remix(&input, &mut output);
+
+        let bands = self.crossovers[0].bands();
+        let mut thresholds = [0.0; MAX_BANDS];
+        let mut ratios = [1.0; MAX_BANDS];
+        let mut makeup = [1.0; MAX_BANDS];
+        for band in 0..bands {
+            thresholds[band] = self.band_param(band, THRESHOLD);
+            ratios[band] = self.band_param(band, RATIO);
+            makeup[band] = db_to_gain(self.band_param(band, MAKEUP));
+        }
+        let master = db_to_gain(self.param[PARAM_MASTER_GAIN]);
+
+        let mut split = [[0.0; MAX_BANDS]; MAX_CHANNELS];
+        let mut deepest = [0.0f32; MAX_BANDS];
+        for frame in output.iter_frames_mut() {
+            let channels = frame.len().min(MAX_CHANNELS);
+            let mut peak = [0.0f32; MAX_BANDS];
+            for ((sample, crossover), out) in frame.iter().zip(&mut self.crossovers).zip(&mut split) {
+                crossover.process(*sample, out);
+                for (peak, x) in peak.iter_mut().zip(&out[..bands]) {
+                    *peak = peak.max(x.abs());
+                }
+            }
+
+            let mut gains = [0.0; MAX_BANDS];
+            for band in 0..bands {
+                let level = self.detectors[band].process(peak[band]);
+                let reduction = compress_db(level, thresholds[band], ratios[band], 0.0) - level;
+                let smoothed = self.envelopes[band].process(reduction);
+                deepest[band] = deepest[band].min(smoothed);
+                gains[band] = db_to_gain(smoothed) * makeup[band];
+            }
+
+            for (sample, bands) in frame[..channels].iter_mut().zip(&split) {
+                *sample = bands.iter().zip(&gains).map(|(x, g)| x * g).sum::<f32>() * master;
+            }
+        }
+
+        for ((meter, envelope), deepest) in self.meters.iter().zip(&self.envelopes).zip(deepest).take(bands) {
+            meter.update(deepest, envelope.value());
+        }
+    }
+
+    fn get_float_buffer(
+        meters: &[GainReductionMeter; MAX_BANDS],
+        parameters: &Parameters,
+        name: &CStr,
+        buffer: &mut [f32],
+    ) {
+        if name.to_bytes() == b"GainReduction" {
+            // Lowest band first; unused bands read as 0.
+            let bands = band_count(parameters.get(PARAM_BANDS).unwrap_or(0.0));
+            for (band, out) in buffer.iter_mut().enumerate().take(MAX_BANDS) {
+                *out = if band < bands { meters[band].take() } else { 0.0 };
+            }
+        }
+    }
+}
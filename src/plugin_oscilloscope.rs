@@ -0,0 +1,176 @@
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use crate::audio_buffer::{remix, Interleaved, InterleavedMut};
+use crate::dsp::ring_buffer::RingBuffer;
+use crate::effect::{Effect, Parameters, ProcessContext};
+use crate::realtime::Mutex;
+use crate::unity_audio_dsp::UnityAudioEffectState_Data;
+
+const PARAM_COUNT: usize = 4;
+const PARAM_WINDOW: usize = 0;
+const PARAM_LEVEL: usize = 1;
+const PARAM_HOLDOFF: usize = 2;
+const PARAM_CHANNEL: usize = 3;
+
+/// Matches the "Window" parameter's maximum.
+const MAX_WINDOW_MS: f32 = 500.0;
+
+/// Room for this many complete captures between two GUI reads.
+const QUEUED_CAPTURES: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Trigger {
+    /// Waiting for a rising edge through the trigger level.
+    Armed,
+    /// Recording a window that started at the trigger.
+    Capturing,
+    /// Ignoring edges for this many more samples after a capture.
+    Holdoff(usize),
+}
+
+/// Pass-through scope that records a window each time the signal rises through the trigger level.
+///
+/// Starting every window at the same point of the waveform is what keeps a periodic signal
+/// standing still on screen. Finished windows are queued as a length followed by the samples, and
+/// "Scope" draws the newest one.
+pub struct Oscilloscope {
+    param: [f32; PARAM_COUNT],
+    trigger: Trigger,
+    previous: f32,
+    capture: Vec<f32>,
+    capture_len: usize,
+    shared: Arc<OscilloscopeShared>,
+}
+
+pub struct OscilloscopeShared {
+    /// Audio thread to GUI thread.
+    captures: RingBuffer<f32>,
+    /// Only ever locked by the GUI thread.
+    display: Mutex<Display>,
+}
+
+/// The newest complete window.
+struct Display {
+    samples: Vec<f32>,
+    len: usize,
+}
+
+impl OscilloscopeShared {
+    /// Moves every complete window out of the queue, keeping the newest.
+    fn receive(&self, display: &mut Display) {
+        while let Some(len) = self.captures.peek() {
+            let len = len as usize;
+            // The audio thread may still be pushing the samples of the window behind this header.
+            if self.captures.len() <= len {
+                break;
+            }
+            self.captures.skip(1);
+            for sample in &mut display.samples[..len] {
+                *sample = self.captures.pop().unwrap_or(0.0);
+            }
+            display.len = len;
+        }
+    }
+
+    /// Resamples the newest window onto `buffer`. The final slot receives the window length in
+    /// samples, or 0 before the first trigger.
+    fn read_scope(&self, buffer: &mut [f32]) {
+        let mut display = self.display.lock();
+        self.receive(&mut display);
+        let Some((count, target)) = buffer.split_last_mut() else {
+            return;
+        };
+        let window = &display.samples[..display.len];
+        let step = window.len().saturating_sub(1) as f32 / target.len().saturating_sub(1).max(1) as f32;
+        for (n, out) in target.iter_mut().enumerate() {
+            let position = n as f32 * step;
+            let i = position as usize;
+            *out = match (window.get(i), window.get(i + 1)) {
+                (Some(a), Some(b)) => a + (b - a) * (position - i as f32),
+                (Some(a), None) => *a,
+                _ => 0.0,
+            };
+        }
+        *count = display.len as f32;
+    }
+}
+
+impl Effect for Oscilloscope {
+    type Shared = OscilloscopeShared;
+
+    fn create(state: &UnityAudioEffectState_Data) -> Self {
+        let max_window = (MAX_WINDOW_MS * 0.001 * state.samplerate as f32) as usize + 1;
+        Oscilloscope {
+            param: [20.0, 0.0, 10.0, 0.0],
+            trigger: Trigger::Armed,
+            previous: 0.0,
+            capture: vec![0.0; max_window],
+            capture_len: 0,
+            shared: Arc::new(OscilloscopeShared {
+                captures: RingBuffer::new(QUEUED_CAPTURES * (max_window + 1)),
+                display: Mutex::new(Display { samples: vec![0.0; max_window], len: 0 }),
+            }),
+        }
+    }
+
+    fn shared(&self) -> Arc<OscilloscopeShared> {
+        self.shared.clone()
+    }
+
+    fn parameters(&mut self) -> &mut [f32] {
+        &mut self.param
+    }
+
+    fn process(&mut self, context: &ProcessContext, input: Interleaved, mut output: InterleavedMut) {
+        remix(&input, &mut output);
+        if output.channels() == 0 {
+            return;
+        }
+
+        let samplerate = context.samplerate as f32;
+        let window = ((self.param[PARAM_WINDOW] * 0.001 * samplerate) as usize).clamp(2, self.capture.len());
+        let holdoff = (self.param[PARAM_HOLDOFF] * 0.001 * samplerate) as usize;
+        let level = self.param[PARAM_LEVEL];
+        let channel = (self.param[PARAM_CHANNEL].round().max(0.0) as usize).min(output.channels() - 1);
+
+        for frame in output.iter_frames_mut() {
+            let sample = frame[channel];
+
+            if self.trigger == Trigger::Armed && self.previous < level && sample >= level {
+                self.trigger = Trigger::Capturing;
+                self.capture_len = 0;
+            }
+
+            match self.trigger {
+                Trigger::Capturing => {
+                    self.capture[self.capture_len] = sample;
+                    self.capture_len += 1;
+                    if self.capture_len >= window {
+                        // Hand over whole windows only; if the GUI has fallen behind, skip this one.
+                        let captures = &self.shared.captures;
+                        let free = captures.capacity() - captures.len();
+                        if free > window {
+                            captures.push(window as f32);
+                            for &s in &self.capture[..window] {
+                                captures.push(s);
+                            }
+                        }
+                        self.trigger = Trigger::Holdoff(holdoff);
+                    }
+                }
+                Trigger::Holdoff(0) => self.trigger = Trigger::Armed,
+                Trigger::Holdoff(remaining) => self.trigger = Trigger::Holdoff(remaining - 1),
+                Trigger::Armed => {}
+            }
+
+            self.previous = sample;
+        }
+    }
+
+    fn get_float_buffer(shared: &OscilloscopeShared, _parameters: &Parameters, name: &CStr, buffer: &mut [f32]) {
+        if name.to_bytes() == b"Scope" {
+            shared.read_scope(buffer);
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use std::ffi::CStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::audio_buffer::{remix, Interleaved, InterleavedMut};
+use crate::dsp::dynamics::db_to_gain;
+use crate::dsp::history::HistoryBuffer;
+use crate::effect::{Effect, Parameters, ProcessContext};
+use crate::unity_audio_dsp::UnityAudioEffectState_Data;
+
+const PARAM_COUNT: usize = 5;
+const PARAM_INDEX: usize = 0;
+const PARAM_MIN_FREQ: usize = 1;
+const PARAM_MAX_FREQ: usize = 2;
+const PARAM_THRESHOLD: usize = 3;
+const PARAM_GATE: usize = 4;
+
+/// Lowest frequency the "Min Freq" parameter allows, which sizes the analysis buffers.
+const LOWEST_FREQ: f32 = 40.0;
+
+/// Number of instances script can tell apart, matching the "Index" parameter's range.
+pub const SLOTS: usize = 64;
+
+/// Latest result of each instance, frequency and confidence packed into one word so script never
+/// sees one from a different analysis than the other.
+static PUBLISHED: [AtomicU64; SLOTS] = [const { AtomicU64::new(0) }; SLOTS];
+
+/// Frequency in Hz (0 when nothing is pitched) and confidence in `[0, 1]` last published to
+/// `index`; zeros for an unused or out-of-range slot.
+pub fn published(index: i32) -> (f32, f32) {
+    let Some(slot) = usize::try_from(index).ok().and_then(|i| PUBLISHED.get(i)) else {
+        return (0.0, 0.0);
+    };
+    unpack(slot.load(Ordering::Relaxed))
+}
+
+fn pack(freq: f32, confidence: f32) -> u64 {
+    ((freq.to_bits() as u64) << 32) | confidence.to_bits() as u64
+}
+
+fn unpack(packed: u64) -> (f32, f32) {
+    (f32::from_bits((packed >> 32) as u32), f32::from_bits(packed as u32))
+}
+
+/// Monophonic pitch tracker using the YIN algorithm (de Cheveigné & Kawahara, 2002).
+///
+/// Every `max_period` samples the last two periods' worth of input are compared against
+/// themselves at each candidate lag. The first lag whose normalized difference dips below the
+/// threshold is the period; `1 - difference` there serves as the confidence.
+pub struct PitchDetector {
+    param: [f32; PARAM_COUNT],
+    history: HistoryBuffer,
+    countdown: usize,
+    /// Analysis scratch: the window, oldest sample first, then the difference function.
+    window: Vec<f32>,
+    difference: Vec<f32>,
+    /// This instance's own latest result, packed the same way, for "Frequency" and "Confidence".
+    result: Arc<AtomicU64>,
+}
+
+impl PitchDetector {
+    /// Lag range in samples for the current parameters, clamped to what the buffers hold.
+    fn lags(&self, samplerate: f32) -> (usize, usize) {
+        let capacity = self.difference.len() - 1;
+        let max_freq = self.param[PARAM_MAX_FREQ].min(0.25 * samplerate);
+        let min_freq = self.param[PARAM_MIN_FREQ].clamp(LOWEST_FREQ, max_freq);
+        let max_lag = ((samplerate / min_freq).ceil() as usize).min(capacity);
+        let min_lag = ((samplerate / max_freq).floor() as usize).clamp(2, max_lag);
+        (min_lag, max_lag)
+    }
+
+    /// Returns the detected frequency and confidence, or zeros for silence or unpitched input.
+    fn analyze(&mut self, samplerate: f32, min_lag: usize, max_lag: usize) -> (f32, f32) {
+        let len = 2 * max_lag;
+        let window = &mut self.window[..len];
+        for (n, sample) in window.iter_mut().enumerate() {
+            *sample = self.history.read(len - 1 - n);
+        }
+
+        let power = window.iter().map(|x| x * x).sum::<f32>() / len as f32;
+        let gate = db_to_gain(self.param[PARAM_GATE]);
+        if power < gate * gate {
+            return (0.0, 0.0);
+        }
+
+        // Cumulative mean normalized difference: d'(0) = 1, d'(tau) = d(tau) * tau / sum(d(1..=tau)).
+        let d = &mut self.difference[..=max_lag];
+        d[0] = 1.0;
+        let mut running = 0.0;
+        for tau in 1..=max_lag {
+            let (head, tail) = (&window[..max_lag], &window[tau..tau + max_lag]);
+            let diff: f32 = head.iter().zip(tail).map(|(a, b)| (a - b) * (a - b)).sum();
+            running += diff;
+            d[tau] = if running > 0.0 { diff * tau as f32 / running } else { 1.0 };
+        }
+
+        // The first dip below the threshold, followed down to its local minimum, avoids picking
+        // a multiple of the period when a later dip happens to be marginally deeper.
+        let threshold = self.param[PARAM_THRESHOLD];
+        let Some(mut tau) = (min_lag..max_lag).find(|&tau| d[tau] < threshold) else {
+            return (0.0, 0.0);
+        };
+        while tau + 1 < max_lag && d[tau + 1] < d[tau] {
+            tau += 1;
+        }
+
+        // Parabolic interpolation between neighbouring lags for sub-sample accuracy.
+        let (a, b, c) = (d[tau - 1], d[tau], d[tau + 1]);
+        let curvature = a - 2.0 * b + c;
+        let offset = if curvature > 0.0 { (0.5 * (a - c) / curvature).clamp(-0.5, 0.5) } else { 0.0 };
+
+        (samplerate / (tau as f32 + offset), (1.0 - b).clamp(0.0, 1.0))
+    }
+}
+
+impl Effect for PitchDetector {
+    type Shared = AtomicU64;
+
+    fn create(state: &UnityAudioEffectState_Data) -> Self {
+        let max_lag = (state.samplerate as f32 / LOWEST_FREQ).ceil() as usize + 1;
+        PitchDetector {
+            param: [0.0, 70.0, 1500.0, 0.15, -60.0],
+            history: HistoryBuffer::new(2 * max_lag),
+            countdown: 0,
+            window: vec![0.0; 2 * max_lag],
+            difference: vec![0.0; max_lag + 1],
+            result: Arc::default(),
+        }
+    }
+
+    fn shared(&self) -> Arc<AtomicU64> {
+        self.result.clone()
+    }
+
+    fn parameters(&mut self) -> &mut [f32] {
+        &mut self.param
+    }
+
+    fn process(&mut self, context: &ProcessContext, input: Interleaved, mut output: InterleavedMut) {
+        remix(&input, &mut output);
+        if output.channels() == 0 {
+            return;
+        }
+
+        let samplerate = context.samplerate as f32;
+        let (min_lag, max_lag) = self.lags(samplerate);
+        let scale = 1.0 / output.channels() as f32;
+
+        for frame in output.iter_frames_mut() {
+            self.history.feed(frame.iter().sum::<f32>() * scale);
+
+            self.countdown = self.countdown.saturating_sub(1);
+            if self.countdown == 0 {
+                let (freq, confidence) = self.analyze(samplerate, min_lag, max_lag);
+                let packed = pack(freq, confidence);
+                self.result.store(packed, Ordering::Relaxed);
+                if let Some(slot) = PUBLISHED.get(self.param[PARAM_INDEX].round().max(0.0) as usize) {
+                    slot.store(packed, Ordering::Relaxed);
+                }
+                self.countdown = max_lag;
+            }
+        }
+    }
+
+    fn get_float_buffer(result: &AtomicU64, _parameters: &Parameters, name: &CStr, buffer: &mut [f32]) {
+        let (freq, confidence) = unpack(result.load(Ordering::Relaxed));
+        let value = match name.to_bytes() {
+            b"Frequency" => freq,
+            b"Confidence" => confidence,
+            _ => return,
+        };
+        if let Some(out) = buffer.first_mut() {
+            *out = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::noise::Random;
+    use crate::effect::testing;
+    use std::f64::consts::TAU;
+
+    const SAMPLERATE: u32 = 48000;
+
+    /// Runs `signal` through a detector publishing to slot `index`, one analysis interval per block,
+    /// and returns what was published after each block along with the time in seconds it refers
+    /// to. The first two analyses, whose windows aren't full yet, are left out.
+    fn track(index: usize, signal: &[f32]) -> Vec<(f64, f32, f32)> {
+        let mut detector = PitchDetector::create(&testing::state(SAMPLERATE));
+        detector.param[PARAM_INDEX] = index as f32;
+        let max_lag = detector.lags(SAMPLERATE as f32).1;
+        let mut output = vec![0.0; max_lag];
+        let mut results = Vec::new();
+        // Each block starts with an analysis of the `2 * max_lag` samples up to its first one.
+        for (block, input) in signal.chunks_exact(max_lag).enumerate() {
+            testing::process(&mut detector, SAMPLERATE, input, &mut output, 1);
+            let (freq, confidence) = published(index as i32);
+            // The difference function at the detected period spans the first `max_lag + period`
+            // samples of the window; its middle is what the estimate describes.
+            let start = (block * max_lag) as f64 + 1.0 - 2.0 * max_lag as f64;
+            let period = if freq > 0.0 { SAMPLERATE as f64 / freq as f64 } else { 0.0 };
+            results.push(((start + 0.5 * (max_lag as f64 + period)) / SAMPLERATE as f64, freq, confidence));
+        }
+        results.split_off(2)
+    }
+
+    /// One second of the tone whose frequency at time `t` is `freq(t)`, with the given harmonic
+    /// amplitudes, fundamental first.
+    fn tone(seconds: f64, freq: impl Fn(f64) -> f64, harmonics: &[f32]) -> Vec<f32> {
+        let mut phase = 0.0;
+        (0..(seconds * SAMPLERATE as f64) as usize)
+            .map(|n| {
+                let sample = harmonics
+                    .iter()
+                    .enumerate()
+                    .map(|(k, amplitude)| amplitude * ((k + 1) as f64 * phase).sin() as f32)
+                    .sum::<f32>();
+                phase = (phase + TAU * freq(n as f64 / SAMPLERATE as f64) / SAMPLERATE as f64) % TAU;
+                0.5 * sample
+            })
+            .collect()
+    }
+
+    fn cents(freq: f32, expected: f64) -> f64 {
+        1200.0 * (freq as f64 / expected).log2()
+    }
+
+    #[test]
+    fn detects_sines() {
+        for (slot, expected) in [82.41, 196.0, 440.0, 1046.5].into_iter().enumerate() {
+            for (t, freq, confidence) in track(slot, &tone(1.0, |_| expected, &[1.0])) {
+                assert!(cents(freq, expected).abs() < 1.0, "{expected} Hz read as {freq} Hz at {t:.3} s");
+                assert!(confidence > 0.99, "{expected} Hz confidence {confidence} at {t:.3} s");
+            }
+        }
+    }
+
+    /// A sawtooth's upper harmonics, or a second harmonic louder than the fundamental, mustn't pull
+    /// the estimate an octave or more up.
+    #[test]
+    fn harmonics_dont_fool_it() {
+        let saw: Vec<f32> = (1..=12).map(|k| 1.0 / k as f32).collect();
+        let cases = [(110.0, &saw[..]), (330.0, &saw[..]), (150.0, &[0.2, 1.0, 0.5][..])];
+        for (slot, (expected, harmonics)) in cases.into_iter().enumerate() {
+            for (t, freq, confidence) in track(10 + slot, &tone(1.0, |_| expected, harmonics)) {
+                assert!(cents(freq, expected).abs() < 1.0, "{expected} Hz read as {freq} Hz at {t:.3} s");
+                assert!(confidence > 0.99, "{expected} Hz confidence {confidence} at {t:.3} s");
+            }
+        }
+    }
+
+    /// A sung note's ±50 cent, 5.5 Hz vibrato, followed analysis by analysis.
+    #[test]
+    fn follows_vibrato() {
+        let vibrato = |t: f64| 220.0 * 2f64.powf(50.0 / 1200.0 * (TAU * 5.5 * t).sin());
+        let results = track(20, &tone(2.0, vibrato, &[1.0, 0.5, 0.25]));
+        for &(t, freq, confidence) in &results {
+            let expected = vibrato(t);
+            assert!(cents(freq, expected).abs() < 5.0, "{expected} Hz read as {freq} Hz at {t:.3} s");
+            assert!(confidence > 0.99, "confidence {confidence} at {t:.3} s");
+        }
+        // Make sure the sweep was actually covered, top and bottom.
+        let (low, high) = results.iter().fold((f32::MAX, 0.0f32), |(low, high), r| (low.min(r.1), high.max(r.1)));
+        assert!(cents(low, 220.0) < -45.0 && cents(high, 220.0) > 45.0, "range {low}..{high} Hz");
+    }
+
+    #[test]
+    fn reports_nothing_for_unpitched_input() {
+        let silence = vec![0.0; SAMPLERATE as usize / 2];
+        let mut random = Random::new(1);
+        let noise: Vec<f32> = (0..SAMPLERATE / 2).map(|_| random.get_float(-0.5, 0.5)).collect();
+        // A clear tone, but below the -60 dB gate.
+        let quiet: Vec<f32> = tone(0.5, |_| 440.0, &[1.0]).iter().map(|x| x * 0.0005).collect();
+        for (slot, signal) in [silence, noise, quiet].iter().enumerate() {
+            for (t, freq, confidence) in track(30 + slot, signal) {
+                assert_eq!((freq, confidence), (0.0, 0.0), "case {slot} at {t:.3} s");
+            }
+        }
+    }
+}
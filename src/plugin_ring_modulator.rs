@@ -1,129 +1,97 @@
-use std::f32::consts::PI;
+use std::sync::Arc;
 
-use crate::unity_audio_dsp::{UnityAudioEffectState, UnityAudioResult};
+use crate::audio_buffer::{remix, Interleaved, InterleavedMut};
+use crate::dsp::oscillator::{Oscillator, Waveform};
+use crate::effect::{Effect, ProcessContext};
+use crate::transport::{NoteDivision, Transport};
+use crate::unity_audio_dsp::UnityAudioEffectState_Data;
 
-const PARAM_COUNT: usize = 2;
+const PARAM_COUNT: usize = 9;
 const PARAM_FREQ: usize = 0;
 const PARAM_MIX: usize = 1;
-
-struct EffectData {
+const PARAM_WAVEFORM: usize = 2;
+const PARAM_LFO_RATE: usize = 3;
+const PARAM_LFO_DEPTH: usize = 4;
+const PARAM_SIDECHAIN: usize = 5;
+const PARAM_SPREAD: usize = 6;
+const PARAM_TEMPO: usize = 7;
+const PARAM_LFO_SYNC: usize = 8;
+
+pub struct RingModulator {
     param: [f32; PARAM_COUNT],
-    sin: f32,
-    cos: f32,
+    carrier: Oscillator,
+    lfo: Oscillator,
 }
 
-unity_dsp_callback!(
-    pub fn create_callback(state: *mut UnityAudioEffectState) -> UnityAudioResult {
-        let effect_data = Box::new(EffectData {
-            param: [1000.0, 0.5],
-            sin: 0.0,
-            cos: 1.0,
-        });
+impl Effect for RingModulator {
+    type Shared = ();
 
-        // dropped in release_callback
-        unsafe {
-            (*state).data.effectdata = Box::leak(effect_data) as *mut EffectData as *mut ();
+    fn create(_state: &UnityAudioEffectState_Data) -> Self {
+        RingModulator {
+            param: [1000.0, 0.5, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            carrier: Oscillator::default(),
+            lfo: Oscillator::default(),
         }
-
-        UnityAudioResult::Ok
     }
-);
 
-unity_dsp_callback!(
-    pub fn release_callback(state: *mut UnityAudioEffectState) -> UnityAudioResult {
-        unsafe {
-            let effect_data: *mut EffectData = (*state).get_effect_data();
-            drop(Box::from_raw(effect_data));
-        }
-
-        UnityAudioResult::Ok
+    fn shared(&self) -> Arc<()> {
+        Arc::default()
     }
-);
-
-unity_dsp_callback!(
-    pub fn set_float_parameter_callback(
-        state: *mut UnityAudioEffectState,
-        index: i32,
-        value: f32,
-    ) -> UnityAudioResult {
-        let data: &mut EffectData = unsafe { (*state).get_effect_data() };
 
-        if index < 0 || index > data.param.len() as i32 {
-            return UnityAudioResult::ErrUnsupported;
-        }
-
-        data.param[index as usize] = value;
-
-        UnityAudioResult::Ok
+    fn parameters(&mut self) -> &mut [f32] {
+        &mut self.param
     }
-);
-
-unity_dsp_callback!(
-    pub fn get_float_parameter_callback(
-        state: *mut UnityAudioEffectState,
-        index: i32,
-        value: *mut f32,
-        value_str: *mut u8,
-    ) -> UnityAudioResult {
-        let data: &mut EffectData = unsafe { (*state).get_effect_data() };
 
-        if index < 0 || index > data.param.len() as i32 {
-            return UnityAudioResult::ErrUnsupported;
+    fn process(&mut self, context: &ProcessContext, input: Interleaved, mut output: InterleavedMut) {
+        let samplerate = context.samplerate as f32;
+        let freq = self.param[PARAM_FREQ];
+        let mix = self.param[PARAM_MIX];
+        let waveform = Waveform::from_param(self.param[PARAM_WAVEFORM]);
+        let depth = self.param[PARAM_LFO_DEPTH];
+
+        // A synced LFO takes both its rate and its phase from the host clock, so it lands on the
+        // beat every block instead of merely running at the right speed.
+        if let Some(division) = NoteDivision::from_param(self.param[PARAM_LFO_SYNC]) {
+            let transport = Transport::new(self.param[PARAM_TEMPO], context.samplerate);
+            self.lfo.set_frequency(transport.hz(division), samplerate);
+            self.lfo.set_phase(transport.phase_at(division, context.currdsptick));
+        } else {
+            self.lfo.set_frequency(self.param[PARAM_LFO_RATE], samplerate);
         }
 
-        if !value.is_null() {
-            unsafe {
-                *value = data.param[index as usize];
+        remix(&input, &mut output);
+
+        // "Robot voice": multiply by whatever another mixer group sends us instead of the carrier.
+        // Without an active send we fall back to the internal oscillator rather than going silent.
+        let sidechain = &context.sidechain;
+        if self.param[PARAM_SIDECHAIN] >= 0.5 && sidechain.channels() > 0 {
+            let shared = sidechain.channels() == output.channels();
+            for (n, frame) in output.iter_frames_mut().enumerate() {
+                let modulator = sidechain.frame(n);
+                let mono = modulator.iter().sum::<f32>() / modulator.len() as f32;
+                for (i, sample) in frame.iter_mut().enumerate() {
+                    let m = if shared { modulator[i] } else { mono };
+                    *sample *= 1.0 - mix + mix * m;
+                }
             }
+            return;
         }
 
-        if !value_str.is_null() {
-            unsafe {
-                *value_str = 0;
-            }
-        }
+        // Each output channel reads the shared carrier at its own phase offset, fanned out evenly
+        // over up to half a cycle, so at full spread a stereo pair runs in antiphase. The noise
+        // carrier is the same on every channel regardless.
+        let spread = 0.5 * self.param[PARAM_SPREAD] as f64 / output.channels().saturating_sub(1).max(1) as f64;
 
-        UnityAudioResult::Ok
-    }
-);
-
-unity_dsp_callback!(
-    pub fn get_float_buffer_callback(
-        state: *mut UnityAudioEffectState,
-        name: *const u8,
-        buffer: *mut f32,
-        num_samples: i32,
-    ) -> UnityAudioResult {
-        UnityAudioResult::Ok
-    }
-);
+        for frame in output.iter_frames_mut() {
+            // The LFO sweeps the carrier by up to `depth` octaves either side of its base frequency.
+            let modulated = freq * (depth * self.lfo.next_sine()).exp2();
+            self.carrier.set_frequency(modulated.min(0.5 * samplerate), samplerate);
 
-unity_dsp_callback!(
-    pub fn process_callback(
-        state: *mut UnityAudioEffectState,
-        in_buffer: *const f32,
-        out_buffer: *mut f32,
-        length: u32,
-        in_channels: i32,
-        out_channels: i32,
-    ) -> UnityAudioResult {
-        let data: &mut EffectData = unsafe { (*state).get_effect_data() };
-        let samplerate = unsafe { (*state).data.samplerate };
-
-        let w = 2.0 * (PI * data.param[PARAM_FREQ] / samplerate as f32).sin();
-
-        for n in 0..length {
-            for i in 0..(out_channels as u32) {
-                let offset = (n * out_channels as u32 + i) as usize;
-                let in_value = unsafe { in_buffer.add(offset).read() };
-                let out_value =
-                    in_value * (1.0 - data.param[PARAM_MIX] + data.param[PARAM_MIX] * data.sin);
-                unsafe { out_buffer.add(offset).write(out_value) };
+            for (i, sample) in frame.iter_mut().enumerate() {
+                let carrier = self.carrier.value_at(waveform, spread * i as f64);
+                *sample *= 1.0 - mix + mix * carrier;
             }
-            data.sin += data.cos * w; // cheap way to calculate a steady sine-wave
-            data.cos -= data.sin * w;
+            self.carrier.advance_waveform(waveform);
         }
-
-        UnityAudioResult::Ok
     }
-);
+}
@@ -0,0 +1,203 @@
+use std::ffi::CStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::audio_buffer::{remix, Interleaved, InterleavedMut};
+use crate::dsp::atomic::AtomicBuffer;
+use crate::dsp::dynamics::{gain_to_db, time_coefficient};
+use crate::dsp::fft::{self, Complex, WindowFunction};
+use crate::effect::{Effect, Parameters, ProcessContext};
+use crate::unity_audio_dsp::UnityAudioEffectState_Data;
+
+const PARAM_COUNT: usize = 6;
+const PARAM_FFT_SIZE: usize = 0; // log2 of the size
+const PARAM_WINDOW: usize = 1;
+const PARAM_AVERAGING: usize = 2;
+const PARAM_PEAK_HOLD: usize = 3;
+const PARAM_PEAK_FALL: usize = 4;
+const PARAM_UPDATE_RATE: usize = 5;
+
+/// 256 to 16384 points, matching the "FFT Size" parameter's range.
+const MIN_FFT_BITS: u32 = 8;
+const MAX_FFT_BITS: u32 = 14;
+const MAX_FFT_SIZE: usize = 1 << MAX_FFT_BITS;
+
+/// Level reported for empty bins and before the first analysis, the floor of `gain_to_db`.
+const FLOOR_DB: f32 = -200.0;
+
+/// Pass-through analyzer for the mix of all channels.
+///
+/// Frames are taken every `1 / Update Rate` seconds from a history of the input, so how often the
+/// spectrum refreshes and how much the frames overlap don't depend on Unity's block size. Bins
+/// are scaled so a full-scale sine reads 0 dB.
+pub struct SpectrumAnalyzer {
+    param: [f32; PARAM_COUNT],
+    size: usize,
+    window_function: WindowFunction,
+    window: Vec<f32>,
+    /// Turns bin magnitudes into sine amplitudes: 2 over the window's sum.
+    window_gain: f32,
+    input: Vec<f32>,
+    write_pos: usize,
+    filled: usize,
+    countdown: usize,
+    fft: Vec<Complex>,
+    /// Exponentially averaged power per bin.
+    average: Vec<f32>,
+    /// Peak-hold level in dB per bin, and how many more frames it holds before falling.
+    peaks: Vec<f32>,
+    hold: Vec<u32>,
+    shared: Arc<SpectrumShared>,
+}
+
+/// Levels in dB per bin as of the latest analysis.
+pub struct SpectrumShared {
+    spectrum: AtomicBuffer,
+    peaks: AtomicBuffer,
+    /// Nyquist bin of the size the levels were analyzed at, or 0 before the first analysis.
+    last_bin: AtomicUsize,
+}
+
+impl SpectrumShared {
+    /// Resamples per-bin dB values from DC to Nyquist onto `buffer`.
+    fn read(&self, levels: &AtomicBuffer, buffer: &mut [f32]) {
+        let last_bin = self.last_bin.load(Ordering::Relaxed);
+        if last_bin == 0 {
+            buffer.fill(FLOOR_DB);
+            return;
+        }
+        let scale = last_bin as f32 / buffer.len().saturating_sub(1).max(1) as f32;
+        for (n, out) in buffer.iter_mut().enumerate() {
+            let f = n as f32 * scale;
+            let i = (f as usize).min(last_bin - 1);
+            let (a, b) = (levels.load(i), levels.load(i + 1));
+            *out = a + (b - a) * (f - i as f32);
+        }
+    }
+}
+
+impl SpectrumAnalyzer {
+    /// Switches FFT size or window, restarting the averages and peaks. The input history is kept.
+    fn configure(&mut self, size: usize, window_function: WindowFunction) {
+        self.size = size;
+        self.window_function = window_function;
+        let window = &mut self.window[..size];
+        window_function.fill(window);
+        self.window_gain = 2.0 / window.iter().sum::<f32>();
+        self.average.fill(0.0);
+        self.peaks.fill(FLOOR_DB);
+        self.hold.fill(0);
+        self.shared.last_bin.store(0, Ordering::Relaxed);
+    }
+
+    fn analyze(&mut self, samplerate: f32, hop: usize) {
+        let size = self.size;
+        let oldest = self.write_pos + MAX_FFT_SIZE - size;
+        for (n, (c, w)) in self.fft[..size].iter_mut().zip(&self.window).enumerate() {
+            *c = Complex::new(self.input[(oldest + n) % MAX_FFT_SIZE] * w, 0.0);
+        }
+        fft::forward(&mut self.fft[..size], true);
+
+        let frame_rate = samplerate / hop as f32;
+        let smoothing = time_coefficient(self.param[PARAM_AVERAGING], frame_rate);
+        let hold_frames = (self.param[PARAM_PEAK_HOLD] * 0.001 * frame_rate) as u32;
+        let fall = self.param[PARAM_PEAK_FALL] / frame_rate;
+
+        let bins = size / 2 + 1;
+        let gain2 = self.window_gain * self.window_gain;
+        let state = self.average.iter_mut().zip(&mut self.peaks).zip(&mut self.hold);
+        for (bin, (((average, peak), hold), c)) in state.take(bins).zip(&self.fft).enumerate() {
+            let power = c.magnitude2() * gain2;
+            *average = power + smoothing * (*average - power);
+
+            let level = 0.5 * gain_to_db(power);
+            if level >= *peak {
+                *peak = level;
+                *hold = hold_frames;
+            } else if *hold > 0 {
+                *hold -= 1;
+            } else {
+                *peak = (*peak - fall).max(level);
+            }
+
+            self.shared.spectrum.store(bin, 0.5 * gain_to_db(*average));
+            self.shared.peaks.store(bin, *peak);
+        }
+        self.shared.last_bin.store(size / 2, Ordering::Relaxed);
+    }
+}
+
+impl Effect for SpectrumAnalyzer {
+    type Shared = SpectrumShared;
+
+    fn create(_state: &UnityAudioEffectState_Data) -> Self {
+        let bins = MAX_FFT_SIZE / 2 + 1;
+        let mut analyzer = SpectrumAnalyzer {
+            param: [11.0, 0.0, 100.0, 1000.0, 12.0, 30.0],
+            size: 0,
+            window_function: WindowFunction::Hann,
+            window: vec![0.0; MAX_FFT_SIZE],
+            window_gain: 0.0,
+            input: vec![0.0; MAX_FFT_SIZE],
+            write_pos: 0,
+            filled: 0,
+            countdown: 0,
+            fft: vec![Complex::default(); MAX_FFT_SIZE],
+            average: vec![0.0; bins],
+            peaks: vec![FLOOR_DB; bins],
+            hold: vec![0; bins],
+            shared: Arc::new(SpectrumShared {
+                spectrum: AtomicBuffer::new(bins),
+                peaks: AtomicBuffer::new(bins),
+                last_bin: AtomicUsize::new(0),
+            }),
+        };
+        analyzer.configure(1 << 11, WindowFunction::Hann);
+        analyzer
+    }
+
+    fn shared(&self) -> Arc<SpectrumShared> {
+        self.shared.clone()
+    }
+
+    fn parameters(&mut self) -> &mut [f32] {
+        &mut self.param
+    }
+
+    fn process(&mut self, context: &ProcessContext, input: Interleaved, mut output: InterleavedMut) {
+        let bits = (self.param[PARAM_FFT_SIZE].round() as u32).clamp(MIN_FFT_BITS, MAX_FFT_BITS);
+        let window_function = WindowFunction::from_param(self.param[PARAM_WINDOW]);
+        if 1 << bits != self.size || window_function != self.window_function {
+            self.configure(1 << bits, window_function);
+        }
+
+        remix(&input, &mut output);
+        if output.channels() == 0 {
+            return;
+        }
+
+        let samplerate = context.samplerate as f32;
+        let hop = ((samplerate / self.param[PARAM_UPDATE_RATE].max(1.0)) as usize).max(1);
+        let scale = 1.0 / output.channels() as f32;
+
+        for frame in output.iter_frames_mut() {
+            self.input[self.write_pos] = frame.iter().sum::<f32>() * scale;
+            self.write_pos = (self.write_pos + 1) % MAX_FFT_SIZE;
+            self.filled = (self.filled + 1).min(MAX_FFT_SIZE);
+
+            self.countdown = self.countdown.saturating_sub(1);
+            if self.countdown == 0 && self.filled >= self.size {
+                self.analyze(samplerate, hop);
+                self.countdown = hop;
+            }
+        }
+    }
+
+    fn get_float_buffer(shared: &SpectrumShared, _parameters: &Parameters, name: &CStr, buffer: &mut [f32]) {
+        match name.to_bytes() {
+            b"Spectrum" => shared.read(&shared.spectrum, buffer),
+            b"Peaks" => shared.read(&shared.peaks, buffer),
+            _ => {}
+        }
+    }
+}
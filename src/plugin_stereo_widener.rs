@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use crate::audio_buffer::{remix, Interleaved, InterleavedMut};
+use crate::dsp::crossover::LinkwitzRiley;
+use crate::dsp::dynamics::db_to_gain;
+use crate::dsp::history::HistoryBuffer;
+use crate::effect::{Effect, ProcessContext};
+use crate::unity_audio_dsp::UnityAudioEffectState_Data;
+
+const PARAM_COUNT: usize = 5;
+const PARAM_WIDTH: usize = 0;
+const PARAM_MID_GAIN: usize = 1;
+const PARAM_SIDE_GAIN: usize = 2;
+const PARAM_BASS_MONO: usize = 3;
+const PARAM_HAAS_DELAY: usize = 4;
+
+/// Matches the "Haas Delay" parameter's range, either way.
+const MAX_HAAS_MS: f32 = 30.0;
+
+/// Mid/side processing for stereo; every other channel layout passes through untouched.
+pub struct StereoWidener {
+    param: [f32; PARAM_COUNT],
+    samplerate: f32,
+    dirty: bool,
+    crossovers: [LinkwitzRiley; 2],
+    /// One delay line per side for the Haas effect; only the lagging side is read delayed.
+    delays: [HistoryBuffer; 2],
+}
+
+impl Effect for StereoWidener {
+    type Shared = ();
+
+    fn create(state: &UnityAudioEffectState_Data) -> Self {
+        let delay_len = (MAX_HAAS_MS * 0.001 * state.samplerate as f32) as usize + 4;
+        StereoWidener {
+            param: [1.0, 0.0, 0.0, 0.0, 0.0],
+            samplerate: state.samplerate as f32,
+            dirty: true,
+            crossovers: [LinkwitzRiley::default(); 2],
+            delays: [HistoryBuffer::new(delay_len), HistoryBuffer::new(delay_len)],
+        }
+    }
+
+    fn shared(&self) -> Arc<()> {
+        Arc::default()
+    }
+
+    fn parameters(&mut self) -> &mut [f32] {
+        &mut self.param
+    }
+
+    fn parameter_changed(&mut self, index: usize) {
+        if index == PARAM_BASS_MONO {
+            self.dirty = true;
+        }
+    }
+
+    fn process(&mut self, context: &ProcessContext, input: Interleaved, mut output: InterleavedMut) {
+        remix(&input, &mut output);
+        if output.channels() != 2 {
+            return;
+        }
+
+        if self.samplerate != context.samplerate as f32 {
+            self.samplerate = context.samplerate as f32;
+            self.dirty = true;
+        }
+        let bass_mono = self.param[PARAM_BASS_MONO] > 0.0;
+        if bass_mono && self.dirty {
+            let freq = self.param[PARAM_BASS_MONO].min(0.45 * self.samplerate);
+            self.crossovers.iter_mut().for_each(|crossover| crossover.setup(freq, self.samplerate));
+            self.dirty = false;
+        }
+
+        let width = self.param[PARAM_WIDTH];
+        let mid_gain = db_to_gain(self.param[PARAM_MID_GAIN]);
+        let side_gain = db_to_gain(self.param[PARAM_SIDE_GAIN]) * width;
+        // Positive delays the right channel, negative the left.
+        let haas = self.param[PARAM_HAAS_DELAY] * 0.001 * self.samplerate;
+        let delays = [(-haas).max(0.0), haas.max(0.0)];
+
+        let [left_xo, right_xo] = &mut self.crossovers;
+        for frame in output.iter_frames_mut() {
+            let (mut left, mut right) = (frame[0], frame[1]);
+
+            // Below the crossover both channels collapse to their sum, so low end stays centred and
+            // mono-compatible however wide the rest gets.
+            let mut low = 0.0;
+            if bass_mono {
+                let (left_low, left_high) = left_xo.split(left);
+                let (right_low, right_high) = right_xo.split(right);
+                low = 0.5 * (left_low + right_low);
+                (left, right) = (left_high, right_high);
+            }
+
+            let mid = (0.5 * (left + right) + low) * mid_gain;
+            let side = 0.5 * (left - right) * side_gain;
+
+            let values = [mid + side, mid - side];
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                self.delays[channel].feed(values[channel]);
+                *sample = self.delays[channel].read_linear(delays[channel]);
+            }
+        }
+    }
+}
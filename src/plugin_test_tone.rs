@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use crate::audio_buffer::Planar;
+use crate::dsp::oscillator::Oscillator;
+use crate::effect::{Effect, ProcessContext};
+use crate::unity_audio_dsp::UnityAudioEffectState_Data;
+
+const PARAM_COUNT: usize = 2;
+const PARAM_FREQ: usize = 0;
+const PARAM_LEVEL: usize = 1;
+
+pub struct TestTone {
+    param: [f32; PARAM_COUNT],
+    oscillator: Oscillator,
+}
+
+impl Effect for TestTone {
+    type Shared = ();
+
+    fn create(_state: &UnityAudioEffectState_Data) -> Self {
+        TestTone {
+            param: [440.0, -12.0],
+            oscillator: Oscillator::default(),
+        }
+    }
+
+    fn shared(&self) -> Arc<()> {
+        Arc::default()
+    }
+
+    fn parameters(&mut self) -> &mut [f32] {
+        &mut self.param
+    }
+
+    const DEINTERLEAVE: bool = true;
+
+    // Generators produce their own signal, so the input block is ignored.
+    fn process_planar(&mut self, context: &ProcessContext, _input: &Planar, output: &mut Planar) {
+        self.oscillator.set_frequency(self.param[PARAM_FREQ], context.samplerate as f32);
+        let gain = 10.0f32.powf(self.param[PARAM_LEVEL] / 20.0);
+
+        let mut channels = output.iter_channels_mut();
+        let Some(first) = channels.next() else {
+            return;
+        };
+
+        for sample in first.iter_mut() {
+            *sample = gain * self.oscillator.next_sine();
+        }
+
+        for run in channels {
+            run.copy_from_slice(first);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Debug-build checks for things that must never happen inside `process`.
+//!
+//! The callback adapter marks the audio thread with an `AudioThreadGuard` for the duration of each
+//! process call. While the marker is set, allocating, freeing or taking a blocking `Mutex::lock` is
+//! reported on stderr instead of silently glitching Unity's mixer, and in unit tests also fails the
+//! test. Release builds compile the allocator hook away.
+
+use std::cell::Cell;
+
+thread_local! {
+    static IN_AUDIO_CALLBACK: Cell<bool> = const { Cell::new(false) };
+}
+
+#[cfg(test)]
+thread_local! {
+    static VIOLATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Marks the current thread as inside an audio callback until dropped.
+pub struct AudioThreadGuard {
+    previous: bool,
+}
+
+impl AudioThreadGuard {
+    pub fn enter() -> Self {
+        let previous = cfg!(debug_assertions) && IN_AUDIO_CALLBACK.with(|flag| flag.replace(true));
+        Self { previous }
+    }
+}
+
+impl Drop for AudioThreadGuard {
+    fn drop(&mut self) {
+        if cfg!(debug_assertions) {
+            IN_AUDIO_CALLBACK.with(|flag| flag.set(self.previous));
+        }
+        // Only the outermost guard panics, so a test can wrap the extern "C" callbacks (which
+        // can't unwind) in a guard of its own and get an ordinary test failure.
+        #[cfg(test)]
+        if !self.previous && !std::thread::panicking() {
+            let violations = VIOLATIONS.with(|count| count.replace(0));
+            assert!(violations == 0, "{violations} real-time violation(s) inside an audio process callback");
+        }
+    }
+}
+
+fn report_violation(what: &str) {
+    // Clear the marker first, otherwise reporting would itself allocate and recurse.
+    let was_inside = IN_AUDIO_CALLBACK.with(|flag| flag.replace(false));
+    if !was_inside {
+        return;
+    }
+
+    eprintln!("real-time violation: {what} inside an audio process callback");
+    // Tests fail on any violation. An allocator must not unwind, so the panic is left to the
+    // guard rather than raised here.
+    #[cfg(test)]
+    VIOLATIONS.with(|count| count.set(count.get() + 1));
+
+    IN_AUDIO_CALLBACK.with(|flag| flag.set(true));
+}
+
+/// Check hook for the allocator; `try_with` because TLS may already be gone during thread exit.
+fn check(what: &str) {
+    if IN_AUDIO_CALLBACK.try_with(Cell::get).unwrap_or(false) {
+        report_violation(what);
+    }
+}
+
+#[cfg(debug_assertions)]
+mod allocator {
+    use std::alloc::{GlobalAlloc, Layout, System};
+
+    struct CheckedAllocator;
+
+    unsafe impl GlobalAlloc for CheckedAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            super::check("allocation");
+            System.alloc(layout)
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            super::check("allocation");
+            System.alloc_zeroed(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            super::check("deallocation");
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            super::check("reallocation");
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CheckedAllocator = CheckedAllocator;
+}
+
+/// `std::sync::Mutex` that flags blocking locks taken on the audio thread.
+///
+/// The audio thread may still use `try_lock`, which never blocks.
+pub struct Mutex<T>(std::sync::Mutex<T>);
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self(std::sync::Mutex::new(value))
+    }
+
+    pub fn lock(&self) -> std::sync::MutexGuard<'_, T> {
+        if cfg!(debug_assertions) {
+            check("blocking lock");
+        }
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    #[allow(dead_code)] // nothing on the audio thread needs GUI-side state yet
+    pub fn try_lock(&self) -> Option<std::sync::MutexGuard<'_, T>> {
+        self.0.try_lock().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "real-time violation")]
+    fn allocation_fails_tests() {
+        let _audio_thread = AudioThreadGuard::enter();
+        std::hint::black_box(Box::new(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "real-time violation")]
+    fn blocking_lock_fails_tests() {
+        let mutex = Mutex::new(0);
+        let _audio_thread = AudioThreadGuard::enter();
+        *mutex.lock() += 1;
+    }
+
+    #[test]
+    fn try_lock_is_allowed() {
+        let mutex = Mutex::new(0);
+        let _audio_thread = AudioThreadGuard::enter();
+        let value = mutex.try_lock();
+        assert!(value.is_some());
+    }
+}
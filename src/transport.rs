@@ -0,0 +1,211 @@
+//! Musical time derived from Unity's DSP clock.
+//!
+//! `currdsptick` counts samples since the mixer started, so together with the sample rate and a
+//! tempo it gives a sample-accurate beat position that never drifts from the game's music system.
+//! The tempo comes either from an effect parameter or from the global transport set by script
+//! through `RustySetTempo`.
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+const DEFAULT_BPM: f32 = 120.0;
+
+static GLOBAL_BPM: AtomicU32 = AtomicU32::new(DEFAULT_BPM.to_bits());
+static GLOBAL_DOWNBEAT: AtomicU64 = AtomicU64::new(0);
+
+/// Sets the tempo every effect follows unless it overrides it, and the DSP tick beat 0 falls on.
+pub fn set_global_tempo(bpm: f32, downbeat_dsptick: u64) {
+    if bpm.is_finite() && bpm > 0.0 {
+        GLOBAL_BPM.store(bpm.to_bits(), Ordering::Relaxed);
+    }
+    GLOBAL_DOWNBEAT.store(downbeat_dsptick, Ordering::Relaxed);
+}
+
+/// A tempo anchored to the DSP clock.
+#[derive(Clone, Copy, Debug)]
+pub struct Transport {
+    bpm: f64,
+    downbeat_dsptick: u64,
+    samplerate: f64,
+}
+
+impl Transport {
+    /// `bpm <= 0` follows the global tempo set by script.
+    pub fn new(bpm: f32, samplerate: u32) -> Self {
+        let (bpm, downbeat_dsptick) = if bpm > 0.0 {
+            (bpm, 0)
+        } else {
+            (f32::from_bits(GLOBAL_BPM.load(Ordering::Relaxed)), GLOBAL_DOWNBEAT.load(Ordering::Relaxed))
+        };
+        Self { bpm: bpm as f64, downbeat_dsptick, samplerate: samplerate.max(1) as f64 }
+    }
+
+    pub fn samples_per_beat(&self) -> f64 {
+        self.samplerate * 60.0 / self.bpm
+    }
+
+    /// Beats (quarter notes) elapsed at `dsptick`; negative before the downbeat.
+    pub fn beat_at(&self, dsptick: u64) -> f64 {
+        (dsptick as f64 - self.downbeat_dsptick as f64) / self.samples_per_beat()
+    }
+
+    /// Position within the current `division`, in cycles `[0, 1)`, at `dsptick`.
+    pub fn phase_at(&self, division: NoteDivision, dsptick: u64) -> f64 {
+        let cycles = self.beat_at(dsptick) / division.beats();
+        cycles - cycles.floor()
+    }
+
+    /// Repetition rate of `division`, for LFOs.
+    pub fn hz(&self, division: NoteDivision) -> f32 {
+        (self.bpm / 60.0 / division.beats()) as f32
+    }
+}
+
+/// A note length such as 1/4, 1/8 dotted or 1/8 triplet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoteDivision {
+    beats: f64,
+}
+
+/// Lengths in beats, indexed by parameter value - 1. Keep in sync with `note_divisions!` in lib.rs.
+const DIVISIONS: [f64; 14] = [
+    4.0,           // 1/1
+    2.0,           // 1/2
+    3.0,           // 1/2 dotted
+    4.0 / 3.0,     // 1/2 triplet
+    1.0,           // 1/4
+    1.5,           // 1/4 dotted
+    2.0 / 3.0,     // 1/4 triplet
+    0.5,           // 1/8
+    0.75,          // 1/8 dotted
+    1.0 / 3.0,     // 1/8 triplet
+    0.25,          // 1/16
+    0.375,         // 1/16 dotted
+    1.0 / 6.0,     // 1/16 triplet
+    0.125,         // 1/32
+];
+
+impl NoteDivision {
+    /// Highest parameter value, for `declare_parameter`.
+    pub const MAX_PARAM: f32 = DIVISIONS.len() as f32;
+
+    /// Maps an enum-style float parameter to a division. 0 means "not synced" and yields `None`.
+    pub fn from_param(value: f32) -> Option<Self> {
+        let index = value.round() as usize;
+        let beats = *DIVISIONS.get(index.checked_sub(1)?)?;
+        Some(Self { beats })
+    }
+
+    /// Length in beats (quarter notes).
+    pub fn beats(&self) -> f64 {
+        self.beats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 120 BPM at 48 kHz: 24000 samples to the beat.
+    fn transport(downbeat_dsptick: u64) -> Transport {
+        Transport { bpm: 120.0, downbeat_dsptick, samplerate: 48000.0 }
+    }
+
+    fn division(param: f32) -> NoteDivision {
+        NoteDivision::from_param(param).unwrap()
+    }
+
+    /// Phases are compared around the circle, so 0.9999999 and 0 count as close.
+    fn assert_phase(actual: f64, expected: f64) {
+        let distance = (actual - expected).rem_euclid(1.0);
+        assert!(distance.min(1.0 - distance) < 1e-9, "phase {actual}, expected {expected}");
+    }
+
+    #[test]
+    fn beats_follow_the_dsp_clock() {
+        let transport = transport(0);
+        assert_eq!(transport.samples_per_beat(), 24000.0);
+        assert_eq!(transport.beat_at(0), 0.0);
+        assert_eq!(transport.beat_at(12000), 0.5);
+        assert_eq!(transport.beat_at(24000 * 7 + 6000), 7.25);
+        // An hour in, still exact to the sample.
+        assert_eq!(transport.beat_at(48000 * 3600 + 1), 7200.0 + 1.0 / 24000.0);
+
+        let quarter = division(5.0);
+        assert_phase(transport.phase_at(quarter, 6000), 0.25);
+        assert_phase(transport.phase_at(quarter, 24000 * 3 + 18000), 0.75);
+        assert_phase(transport.phase_at(division(1.0), 24000 * 6), 0.5);
+    }
+
+    #[test]
+    fn downbeat_offsets_the_phase() {
+        let transport = transport(1000);
+        assert_eq!(transport.beat_at(1000), 0.0);
+        assert_eq!(transport.beat_at(0), -1000.0 / 24000.0);
+
+        let quarter = division(5.0);
+        assert_phase(transport.phase_at(quarter, 1000), 0.0);
+        assert_phase(transport.phase_at(quarter, 7000), 0.25);
+        // Before the downbeat the phase still counts up towards it.
+        assert_phase(transport.phase_at(quarter, 0), 1.0 - 1000.0 / 24000.0);
+        assert!((0.0..1.0).contains(&transport.phase_at(quarter, 0)));
+    }
+
+    /// An LFO running at `hz` from the phase at one block's start arrives where the next block
+    /// picks it up, so re-syncing each block never jumps.
+    #[test]
+    fn phase_is_continuous_across_blocks() {
+        let transport = transport(777);
+        for param in [5.0, 9.0, 10.0, 13.0] {
+            let division = division(param);
+            let step = transport.hz(division) as f64 / 48000.0;
+            for start in (0..200_000).step_by(512) {
+                let advanced = transport.phase_at(division, start) + 512.0 * step;
+                assert_phase(transport.phase_at(division, start + 512), advanced);
+            }
+        }
+    }
+
+    #[test]
+    fn dotted_and_triplet_divisions() {
+        assert_eq!(NoteDivision::from_param(0.0), None);
+        assert_eq!(NoteDivision::from_param(NoteDivision::MAX_PARAM + 1.0), None);
+
+        // (parameter, beats, Hz at 120 BPM)
+        let cases = [
+            (5.0, 1.0, 2.0),          // 1/4
+            (6.0, 1.5, 4.0 / 3.0),    // 1/4 dotted
+            (7.0, 2.0 / 3.0, 3.0),    // 1/4 triplet
+            (9.0, 0.75, 8.0 / 3.0),   // 1/8 dotted
+            (10.0, 1.0 / 3.0, 6.0),   // 1/8 triplet
+            (13.0, 1.0 / 6.0, 12.0),  // 1/16 triplet
+        ];
+        let transport = transport(0);
+        for (param, beats, hz) in cases {
+            let division = division(param);
+            assert_eq!(division.beats(), beats, "parameter {param}");
+            let actual = transport.hz(division);
+            assert!((actual - hz).abs() < 1e-5, "parameter {param}: {actual} Hz");
+        }
+
+        // A 1/8 triplet wraps three times a beat; a dotted 1/8 every 18000 samples.
+        let triplet = division(10.0);
+        assert_phase(transport.phase_at(triplet, 8000), 0.0);
+        assert_phase(transport.phase_at(triplet, 12000), 0.5);
+        let dotted = division(9.0);
+        assert_phase(transport.phase_at(dotted, 18000), 0.0);
+        assert_phase(transport.phase_at(dotted, 27000), 0.5);
+    }
+
+    #[test]
+    fn zero_tempo_follows_the_global_transport() {
+        set_global_tempo(90.0, 4800);
+        let transport = Transport::new(0.0, 48000);
+        assert_eq!(transport.samples_per_beat(), 32000.0);
+        assert_eq!(transport.beat_at(4800 + 16000), 0.5);
+        // A nonsensical tempo from script keeps the previous one.
+        set_global_tempo(f32::NAN, 0);
+        assert_eq!(Transport::new(0.0, 48000).samples_per_beat(), 32000.0);
+        // A tempo of its own ignores script entirely.
+        assert_eq!(Transport::new(120.0, 48000).beat_at(24000), 1.0);
+    }
+}
@@ -1,3 +1,6 @@
+// Field and type names mirror AudioPluginInterface.h so the two can be compared side by side.
+#![allow(non_camel_case_types, non_snake_case, non_upper_case_globals)]
+
 pub const UNITY_AUDIO_PLUGIN_API_VERSION: u32 = 0x010402;
 
 #[macro_use]
@@ -16,9 +19,9 @@ mod windows {
 mod unix {
     #[macro_export]
     macro_rules! unity_dsp_callback {
-        (export $($t:tt)*) => { #[no_mangle] pub extern $($t)* };
-        (pub $($t:tt)*) => { pub extern $($t)* };
-        ($($t:tt)*) => { extern $($t)* };
+        (export $($t:tt)*) => { #[no_mangle] pub extern "C" $($t)* };
+        (pub $($t:tt)*) => { pub extern "C" $($t)* };
+        ($($t:tt)*) => { extern "C" $($t)* };
     }
 }
 
@@ -29,6 +32,12 @@ pub enum UnityAudioResult {
     ErrUnsupported = 1,
 }
 
+// enum UnityAudioEffectDefinitionFlags
+pub const UnityAudioEffectDefinitionFlags_IsSideChainTarget: u64 = 1 << 0;   // Does this effect need a side chain buffer and can it be targeted by a Send?
+
+// enum UnityAudioEffectStateFlags
+pub const UnityAudioEffectStateFlags_IsSideChainTarget: u32 = 1 << 3;        // Does this effect need a side chain buffer and can it be targeted by a Send?
+
 #[repr(C)]
 pub union UnityAudioEffectState {
     pub data: UnityAudioEffectState_Data,
@@ -36,9 +45,10 @@ pub union UnityAudioEffectState {
 }
 
 impl UnityAudioEffectState {
-    pub unsafe fn get_effect_data<'a, T>(&'a mut self) -> &'a mut T {
-        let ptr = self.data.effectdata as *mut T;
-        &mut *ptr
+    /// Shared access only: the GUI and audio threads call into the same instance concurrently.
+    pub unsafe fn get_effect_data<T>(&self) -> &T {
+        let ptr = self.data.effectdata as *const T;
+        &*ptr
     }
 }
 